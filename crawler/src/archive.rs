@@ -0,0 +1,303 @@
+use crate::config::{Config, ArchiveConfig};
+use chrono::NaiveDateTime;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use format::giveaway::SearchResult;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::{fs::{self, File}, io, path::PathBuf};
+
+/// Everything gleam.io told about a campaign when it was loaded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub key: String,
+    pub fetched_at: u64,
+    pub entry_count: Option<usize>,
+    /// The complete campaign, including the fields we do not use
+    pub giveaway: Value,
+}
+
+impl Snapshot {
+    pub fn new(giveaway: &SearchResult, snapshot: Value) -> Snapshot {
+        Snapshot {
+            key: giveaway.giveaway.campaign.key.clone(),
+            fetched_at: giveaway.last_updated,
+            entry_count: giveaway.entry_count,
+            giveaway: snapshot,
+        }
+    }
+}
+
+/// Snapshots are saved as `<folder>/<key>/<timestamp>.json.gz`.
+fn snapshot_path(folder: &str, key: &str, fetched_at: u64) -> PathBuf {
+    PathBuf::from(folder).join(key).join(format!("{}.json.gz", fetched_at))
+}
+
+/// Get the keys of the campaigns which have snapshots.
+pub fn archived_keys(folder: &str) -> Vec<String> {
+    let folder = match fs::read_dir(folder) {
+        Ok(folder) => folder,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut keys = folder
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect::<Vec<String>>();
+    keys.sort_unstable();
+    keys
+}
+
+/// Get the timestamps of the snapshots of a campaign, oldest first.
+pub fn list_snapshots(folder: &str, key: &str) -> Vec<u64> {
+    let folder = match fs::read_dir(PathBuf::from(folder).join(key)) {
+        Ok(folder) => folder,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut timestamps = folder
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter_map(|filename| filename.strip_suffix(".json.gz").and_then(|t| t.parse::<u64>().ok()))
+        .collect::<Vec<u64>>();
+    timestamps.sort_unstable();
+    timestamps
+}
+
+pub fn read_snapshot(folder: &str, key: &str, fetched_at: u64) -> io::Result<Snapshot> {
+    let file = File::open(snapshot_path(folder, key, fetched_at))?;
+    Ok(serde_json::from_reader(GzDecoder::new(file))?)
+}
+
+fn write_snapshot(folder: &str, snapshot: &Snapshot) -> io::Result<()> {
+    let path = snapshot_path(folder, &snapshot.key, snapshot.fetched_at);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    serde_json::to_writer(&mut encoder, snapshot)?;
+    encoder.finish()?.sync_all()
+}
+
+/// Archive a snapshot, unless neither the campaign nor its number of entries changed since the previous one.
+/// Returns true if the snapshot was saved.
+pub fn save_snapshot(archive_config: &ArchiveConfig, snapshot: &Snapshot) -> bool {
+    if let Some(last) = list_snapshots(&archive_config.folder, &snapshot.key).last() {
+        match read_snapshot(&archive_config.folder, &snapshot.key, *last) {
+            Ok(last) if last.giveaway == snapshot.giveaway && last.entry_count == snapshot.entry_count => return false,
+            Ok(_) => (),
+            Err(e) => eprintln!("Unable to read the last snapshot of {}: {}", snapshot.key, e),
+        }
+    }
+
+    match write_snapshot(&archive_config.folder, snapshot) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to archive a snapshot of {}: {}", snapshot.key, e);
+            false
+        }
+    }
+}
+
+/// A modification of a campaign between two snapshots.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    EntryMethodAdded { id: String, entry_type: String },
+    EntryMethodRemoved { id: String, entry_type: String },
+    EntryMethodEdited { id: String, entry_type: String },
+    EndDate { old: u64, new: u64 },
+    /// A short field changed
+    Field { name: &'static str, old: Value, new: Value },
+    /// A long text changed
+    Text { name: &'static str },
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    NaiveDateTime::from_timestamp(timestamp as i64, 0).format("%Y-%m-%d %H:%M").to_string()
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::EntryMethodAdded { id, entry_type } => write!(f, "+ entry method {} ({})", id, entry_type),
+            Change::EntryMethodRemoved { id, entry_type } => write!(f, "- entry method {} ({})", id, entry_type),
+            Change::EntryMethodEdited { id, entry_type } => write!(f, "~ entry method {} ({})", id, entry_type),
+            Change::EndDate { old, new } if new > old => write!(f, "end date: {} -> {} (extended by {} hours)", format_timestamp(*old), format_timestamp(*new), (new - old) / 3600),
+            Change::EndDate { old, new } => write!(f, "end date: {} -> {} (shortened by {} hours)", format_timestamp(*old), format_timestamp(*new), (old - new) / 3600),
+            Change::Field { name, old, new } => write!(f, "{}: {} -> {}", name, old, new),
+            Change::Text { name } => write!(f, "{} edited", name),
+        }
+    }
+}
+
+/// Fields compared by `diff`, with the JSON pointers to their values.
+const FIELDS: [(&str, &str); 5] = [
+    ("name", "/campaign/name"),
+    ("start date", "/campaign/starts_at"),
+    ("paused", "/campaign/paused"),
+    ("finished", "/campaign/finished"),
+    ("prize", "/incentive/name"),
+];
+const TEXTS: [(&str, &str); 2] = [
+    ("prize description", "/incentive/description"),
+    ("terms and conditions", "/campaign/terms_and_conditions"),
+];
+
+/// List the changes of a campaign between two snapshots.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let ends_at = |giveaway: &Value| giveaway.pointer("/campaign/ends_at").and_then(|t| t.as_u64());
+    if let (Some(old), Some(new)) = (ends_at(old), ends_at(new)) {
+        if old != new {
+            changes.push(Change::EndDate { old, new });
+        }
+    }
+
+    for (name, pointer) in FIELDS.iter() {
+        let (old, new) = (old.pointer(pointer), new.pointer(pointer));
+        if old != new {
+            changes.push(Change::Field {
+                name,
+                old: old.cloned().unwrap_or(Value::Null),
+                new: new.cloned().unwrap_or(Value::Null),
+            });
+        }
+    }
+
+    for (name, pointer) in TEXTS.iter() {
+        if old.pointer(pointer) != new.pointer(pointer) {
+            changes.push(Change::Text { name });
+        }
+    }
+
+    let entry_methods = |giveaway: &Value| -> Vec<(String, String, Value)> {
+        giveaway["entry_methods"].as_array().map(|methods| methods.iter().map(|method| (
+            method["id"].as_str().map(String::from).unwrap_or_else(|| method["id"].to_string()),
+            method["entry_type"].as_str().unwrap_or("unknown").to_string(),
+            method.clone(),
+        )).collect()).unwrap_or_default()
+    };
+    let (old_methods, new_methods) = (entry_methods(old), entry_methods(new));
+    for (id, entry_type, method) in &new_methods {
+        match old_methods.iter().find(|(old_id, _, _)| old_id == id) {
+            None => changes.push(Change::EntryMethodAdded { id: id.clone(), entry_type: entry_type.clone() }),
+            Some((_, _, old_method)) if old_method != method => changes.push(Change::EntryMethodEdited { id: id.clone(), entry_type: entry_type.clone() }),
+            Some(_) => (),
+        }
+    }
+    for (id, entry_type, _) in &old_methods {
+        if !new_methods.iter().any(|(new_id, _, _)| new_id == id) {
+            changes.push(Change::EntryMethodRemoved { id: id.clone(), entry_type: entry_type.clone() });
+        }
+    }
+
+    changes
+}
+
+/// Print the changes of a campaign between two snapshots, or over its whole history if they are not specified.
+pub fn print_diff(key: &str, from: Option<u64>, to: Option<u64>, config: &Config) {
+    let archive_config = match &config.archive {
+        Some(archive_config) => archive_config,
+        None => {
+            eprintln!("The archive must be enabled in the configuration file to compare snapshots.");
+            std::process::exit(1);
+        }
+    };
+
+    let snapshots = list_snapshots(&archive_config.folder, key);
+    let pairs = match (from, to) {
+        (Some(from), Some(to)) => vec![(from, to)],
+        (Some(from), None) => vec![(from, *snapshots.last().unwrap_or(&from))],
+        (None, _) => snapshots.windows(2).map(|pair| (pair[0], pair[1])).collect(),
+    };
+    if pairs.is_empty() {
+        println!("There are {} snapshots of {}, nothing to compare.", snapshots.len(), key);
+        return;
+    }
+
+    for (from, to) in pairs {
+        let (old, new) = match (read_snapshot(&archive_config.folder, key, from), read_snapshot(&archive_config.folder, key, to)) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Can't read the snapshots of {}: {}", key, e);
+                std::process::exit(1);
+            }
+        };
+
+        println!("{} -> {}", format_timestamp(from), format_timestamp(to));
+        if old.entry_count != new.entry_count {
+            println!("  entries: {} -> {}", old.entry_count.map(|c| c.to_string()).unwrap_or_else(|| String::from("unknown")), new.entry_count.map(|c| c.to_string()).unwrap_or_else(|| String::from("unknown")));
+        }
+        let changes = diff(&old.giveaway, &new.giveaway);
+        if changes.is_empty() {
+            println!("  no change");
+        }
+        for change in changes {
+            println!("  {}", change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn campaign(ends_at: u64, paused: bool, description: &str, entry_methods: Value) -> Value {
+        json!({
+            "campaign": {"key": "abcde", "name": "campaign name", "starts_at": 0, "ends_at": ends_at, "paused": paused, "finished": false, "terms_and_conditions": ""},
+            "incentive": {"name": "prize", "description": description},
+            "entry_methods": entry_methods,
+        })
+    }
+
+    #[test]
+    fn diffing() {
+        let old = campaign(3600, false, "a prize", json!([{"id": 1, "entry_type": "twitter_follow", "worth": 1}, {"id": 2, "entry_type": "email_subscribe", "worth": 1}]));
+        assert!(diff(&old, &old).is_empty());
+
+        let new = campaign(7 * 3600, true, "a better prize", json!([{"id": 1, "entry_type": "twitter_follow", "worth": 5}, {"id": 3, "entry_type": "youtube_visit_channel", "worth": 1}]));
+        let changes = diff(&old, &new);
+        assert_eq!(changes, vec![
+            Change::EndDate { old: 3600, new: 7 * 3600 },
+            Change::Field { name: "paused", old: json!(false), new: json!(true) },
+            Change::Text { name: "prize description" },
+            Change::EntryMethodEdited { id: String::from("1"), entry_type: String::from("twitter_follow") },
+            Change::EntryMethodAdded { id: String::from("3"), entry_type: String::from("youtube_visit_channel") },
+            Change::EntryMethodRemoved { id: String::from("2"), entry_type: String::from("email_subscribe") },
+        ]);
+        assert_eq!(changes[0].to_string(), "end date: 1970-01-01 01:00 -> 1970-01-01 07:00 (extended by 6 hours)");
+    }
+
+    #[test]
+    fn archiving() {
+        let folder = std::env::temp_dir().join(format!("gleam_finder_archive_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let archive_config = ArchiveConfig {
+            folder: folder.to_str().unwrap().to_string(),
+        };
+        let snapshot = |fetched_at: u64, ends_at: u64, entry_count: usize| Snapshot {
+            key: String::from("abcde"),
+            fetched_at,
+            entry_count: Some(entry_count),
+            giveaway: campaign(ends_at, false, "a prize", json!([])),
+        };
+
+        assert!(save_snapshot(&archive_config, &snapshot(10, 3600, 42)));
+        // Nothing changed
+        assert!(!save_snapshot(&archive_config, &snapshot(20, 3600, 42)));
+        assert!(save_snapshot(&archive_config, &snapshot(30, 7200, 42)));
+        // Only the number of entries changed
+        assert!(save_snapshot(&archive_config, &snapshot(40, 7200, 50)));
+        assert_eq!(list_snapshots(&archive_config.folder, "abcde"), vec![10, 30, 40]);
+        assert!(list_snapshots(&archive_config.folder, "fghij").is_empty());
+        assert_eq!(archived_keys(&archive_config.folder), vec!["abcde"]);
+
+        let old = read_snapshot(&archive_config.folder, "abcde", 10).unwrap();
+        let new = read_snapshot(&archive_config.folder, "abcde", 30).unwrap();
+        assert_eq!(diff(&old.giveaway, &new.giveaway), vec![Change::EndDate { old: 3600, new: 7200 }]);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
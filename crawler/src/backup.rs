@@ -1,17 +1,15 @@
-use crate::config::BackupConfig;
+use crate::{config::BackupConfig, database::{save_json, tombstones_path}, store::{GiveawayStore, Tombstone}};
+use format::giveaway::SearchResult;
 use std::fs::*;
 use std::time::UNIX_EPOCH;
 use std::path::PathBuf;
 use chrono::{DateTime, Utc};
-use std::io::prelude::*;
 
-pub fn backup(database_filename: &str, backup_config: &BackupConfig) {
-    let folder = match read_dir(&backup_config.folder) {
+/// List the backups of a folder with their modification timestamps.
+fn list_backups(folder: &str) -> Vec<(PathBuf, u64)> {
+    let folder = match read_dir(folder) {
         Ok(folder) => folder,
-        Err(_) => {
-            let _ = create_dir(&backup_config.folder);
-            read_dir(&backup_config.folder).expect("Failed to read the backup folder")
-        }
+        Err(_) => return Vec::new(),
     };
 
     let mut backups: Vec<(PathBuf, u64)> = Vec::new();
@@ -44,15 +42,33 @@ pub fn backup(database_filename: &str, backup_config: &BackupConfig) {
             }
         };
 
-        if filename.starts_with("crawler_backup_") && filename.ends_with(".json") {
+        // The tombstones of a backup are saved next to it
+        if filename.starts_with("crawler_backup_") && filename.ends_with(".json") && !filename.ends_with(".tombstones.json") {
             backups.push((path, modified))
         }
     }
+    backups
+}
+
+/// Get the paths of the backups, starting with the most recent one.
+pub fn backups_newest_first(folder: &str) -> Vec<PathBuf> {
+    let mut backups = list_backups(folder);
+    backups.sort_unstable_by_key(|(_p, t)| std::cmp::Reverse(*t));
+    backups.into_iter().map(|(path, _t)| path).collect()
+}
+
+/// Save a copy of the database and of its tombstones as JSON files, removing the oldest backups if there are too many.
+pub fn backup(giveaways: &dyn GiveawayStore, backup_config: &BackupConfig) {
+    if read_dir(&backup_config.folder).is_err() {
+        create_dir(&backup_config.folder).expect("Failed to create the backup folder");
+    }
+    let mut backups = list_backups(&backup_config.folder);
 
     if backups.len() >= backup_config.max {
         backups.sort_unstable_by_key(|(_p, t)| *t);
         while backups.len() >= backup_config.max {
             let (path, _t) = backups.remove(0);
+            let _ = remove_file(tombstones_path(&path.to_string_lossy()));
             let _ = remove_file(path);
         }
     }
@@ -60,8 +76,10 @@ pub fn backup(database_filename: &str, backup_config: &BackupConfig) {
     let now: DateTime<Utc> = Utc::now();
     let path = format!("{}/crawler_backup_{}.json", backup_config.folder, now.format("%R-%d-%b-%C"));
 
-    let mut file = File::create(&path).expect("Unable to create backup file");
-    file.write_all(b"This data will disappear...").unwrap();
-
-    copy(database_filename, path).unwrap();
+    // Tombstones are written first, so that a backup is never listed without them
+    let tombstones = giveaways.iter_tombstones().collect::<Vec<Tombstone>>();
+    if !tombstones.is_empty() {
+        save_json(tombstones.iter(), &tombstones_path(&path));
+    }
+    save_json(giveaways.iter_all().collect::<Vec<SearchResult>>().iter(), &path);
 }
\ No newline at end of file
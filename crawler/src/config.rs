@@ -1,12 +1,36 @@
 use serde::{Serialize, Deserialize};
+use crate::{gleam::ParseOptions, retry::RetryPolicy};
+use format::{classifier::{set_rules, RuleSet}, strictness::Strictness};
 use std::fs::File;
 use std::io::{prelude::*, stdin};
 
 mod defaults {
     pub(super) const fn cooldown() -> usize {7}
     pub(super) const fn timeout() -> usize {10}
+    pub(super) const fn concurrency() -> usize {8}
     pub(super) const fn r#true() -> bool {true}
     pub(super) fn database_file() -> String {String::from("giveaways.json")}
+    pub(super) const fn database_backend() -> super::DatabaseBackend {super::DatabaseBackend::Json}
+    pub(super) fn sitemap_state_file() -> String {String::from("sitemaps.json")}
+    pub(super) fn status_file() -> String {String::from("status.json")}
+    pub(super) fn schema_file() -> String {String::from("schema.json")}
+    pub(super) fn hourly() -> String {String::from("0 * * * *")}
+    pub(super) fn schedules() -> super::SchedulesConfig {
+        super::SchedulesConfig {
+            discovery: hourly(),
+            updates: hourly(),
+            meilisearch: hourly(),
+            backups: None,
+        }
+    }
+    pub(super) fn sources() -> super::SourcesConfig {
+        super::SourcesConfig {
+            google: Some(super::GoogleSourceConfig { enabled: true }),
+            seeds: None,
+            feeds: None,
+            sitemaps: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -25,6 +49,70 @@ pub struct BackupConfig {
     pub max: usize,
 }
 
+/// Where complete snapshots of the campaigns are saved each time they change.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ArchiveConfig {
+    pub folder: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    Json,
+    Sqlite,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GoogleSourceConfig {
+    #[serde(default = "defaults::r#true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SeedsSourceConfig {
+    #[serde(default = "defaults::r#true")]
+    pub enabled: bool,
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FeedsSourceConfig {
+    #[serde(default = "defaults::r#true")]
+    pub enabled: bool,
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SitemapsSourceConfig {
+    #[serde(default = "defaults::r#true")]
+    pub enabled: bool,
+    pub sites: Vec<String>,
+    #[serde(default = "defaults::sitemap_state_file")]
+    pub state_file: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SourcesConfig {
+    pub google: Option<GoogleSourceConfig>,
+    pub seeds: Option<SeedsSourceConfig>,
+    pub feeds: Option<FeedsSourceConfig>,
+    pub sitemaps: Option<SitemapsSourceConfig>,
+}
+
+/// Cron expressions (`minute hour day-of-month month day-of-week`, in UTC) telling when the jobs of the daemon run.
+/// Every job also runs once when the daemon starts.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchedulesConfig {
+    #[serde(default = "defaults::hourly")]
+    pub discovery: String,
+    #[serde(default = "defaults::hourly")]
+    pub updates: String,
+    #[serde(default = "defaults::hourly")]
+    pub meilisearch: String,
+    /// Backups are made every `backups.interval` hours if this is not set
+    pub backups: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default = "defaults::cooldown")]
@@ -33,12 +121,47 @@ pub struct Config {
     pub update: usize,
     #[serde(default = "defaults::timeout")]
     pub timeout: usize,
+    /// The maximum number of pages loaded at the same time
+    #[serde(default = "defaults::concurrency")]
+    pub concurrency: usize,
     #[serde(default)]
     pub blame_useless_pages: bool,
     #[serde(default = "defaults::database_file")]
     pub database_file: String,
+    #[serde(default = "defaults::database_backend")]
+    pub database_backend: DatabaseBackend,
+    /// A JSON file in which the daemon reports when each job last ran
+    #[serde(default = "defaults::status_file")]
+    pub status_file: String,
+    /// The baseline of the `schema` command
+    #[serde(default = "defaults::schema_file")]
+    pub schema_file: String,
+    /// Whether pages with fields we do not know are rejected ("strict") or kept ("lenient")
+    #[serde(default)]
+    pub strictness: Strictness,
+    /// A folder in which the pages which can't be parsed are saved, to be replayed as test cases
+    pub quarantine_folder: Option<String>,
+    /// A TOML or JSON file replacing the built-in rules of the classifier
+    pub rules_file: Option<String>,
     pub backups: Option<BackupConfig>,
+    pub archive: Option<ArchiveConfig>,
     pub meilisearch: Option<MeiliSearchConfig>,
+    #[serde(default = "defaults::sources")]
+    pub sources: SourcesConfig,
+    #[serde(default = "defaults::schedules")]
+    pub schedules: SchedulesConfig,
+    /// How to react when gleam.io fails
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+impl Config {
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            strictness: self.strictness,
+            quarantine_folder: self.quarantine_folder.clone(),
+        }
+    }
 }
 
 pub fn read_config(path: &str) -> Config {
@@ -57,6 +180,17 @@ pub fn read_config(path: &str) -> Config {
             panic!("Your configuration file is not valid: {}\nYou may want to use the `configurate` command to generate a configuration file.", e);
         }
     };
+
+    if let Some(rules_file) = &config.rules_file {
+        let rules = match RuleSet::load(rules_file) {
+            Ok(rules) => rules,
+            Err(e) => panic!("The rules file {:?} is not valid: {}", rules_file, e),
+        };
+        if set_rules(rules).is_err() {
+            panic!("The rules of the classifier are already set");
+        }
+    }
+
     config
 }
 
@@ -101,7 +235,12 @@ pub fn configurate() {
 
     println!("Welcome! Let's generate your configuration file.\n");
 
-    let database_file = input("In which file do you want to save the data (json)?");
+    let database_backend = if ask("Do you want to store the data in a SQLite database (recommended for large databases)?", false) {
+        DatabaseBackend::Sqlite
+    } else {
+        DatabaseBackend::Json
+    };
+    let database_file = input("In which file do you want to save the data?");
     println!("Great! Data will be saved in {:?}.", database_file);
     println!();
 
@@ -135,26 +274,116 @@ pub fn configurate() {
         None
     };
 
-    let (timeout, cooldown) = if ask("Do you want to use custom values for timeout and cooldown?", false) {
+    let archive = if ask("Do you want to archive complete snapshots of the campaigns to see how they are edited?", false) {
+        let folder = input("In which folder?");
+        println!();
+        Some(ArchiveConfig {
+            folder,
+        })
+    } else {
+        None
+    };
+
+    let google = Some(GoogleSourceConfig {
+        enabled: ask("Do you want to search gleam.io links on Google?", true),
+    });
+    let seeds = if ask("Do you want to crawl a fixed list of pages?", false) {
+        let urls = input("Enter the urls of these pages (separated by commas).");
+        println!();
+        Some(SeedsSourceConfig {
+            enabled: true,
+            urls: urls.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect(),
+        })
+    } else {
+        None
+    };
+
+    let feeds = if ask("Do you want to poll RSS or Atom feeds?", false) {
+        let urls = input("Enter the urls of these feeds (separated by commas).");
+        println!();
+        Some(FeedsSourceConfig {
+            enabled: true,
+            urls: urls.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect(),
+        })
+    } else {
+        None
+    };
+
+    let sitemaps = if ask("Do you want to read the sitemaps of giveaway aggregators?", false) {
+        let sites = input("Enter the urls of these sites (separated by commas).");
+        println!();
+        Some(SitemapsSourceConfig {
+            enabled: true,
+            sites: sites.split(',').map(|site| site.trim().to_string()).filter(|site| !site.is_empty()).collect(),
+            state_file: String::from("sitemaps.json"),
+        })
+    } else {
+        None
+    };
+
+    let (timeout, cooldown, concurrency) = if ask("Do you want to use custom values for timeout, cooldown and concurrency?", false) {
         let timeout = input_usize("Enter the maximum duration of an HTTP request in seconds.");
         let cooldown = input_usize("Enter the time to wait between two HTTP requests to the same domain in seconds.");
-        (timeout, cooldown)
+        let concurrency = input_usize("Enter the maximum number of pages to load at the same time.");
+        (timeout, cooldown, concurrency)
     } else {
-        (10, 7)
+        (10, 7, 8)
     };
 
-    let update = input_usize("How many giveaways to you want to update per hour? (can be 0)");
+    let schedules = if ask("Do you want to customize when the crawler searches and updates giveaways? (every hour by default)", false) {
+        println!("Schedules are cron expressions (minute hour day-of-month month day-of-week, in UTC), for example \"0 * * * *\" for every hour.");
+        let discovery = input("When do you want to search for new giveaways?");
+        let updates = input("When do you want to update known giveaways?");
+        let meilisearch = input("When do you want to update the MeiliSearch index?");
+        let backups = if backups.is_some() {
+            Some(input("When do you want to make backups?"))
+        } else {
+            None
+        };
+        println!();
+        SchedulesConfig {
+            discovery,
+            updates,
+            meilisearch,
+            backups,
+        }
+    } else {
+        defaults::schedules()
+    };
+
+    let update = input_usize("How many giveaways to you want to update each time? (can be 0)");
     let blame_useless_pages = ask("Do you want the crawler to report useless pages?", false);
+    let strictness = if ask("Do you want to reject the pages containing fields the crawler does not know (to detect changes of gleam.io)?", false) {
+        Strictness::Strict
+    } else {
+        Strictness::Lenient
+    };
     println!();
 
     let config = Config {
         timeout,
         cooldown,
+        concurrency,
         update,
         blame_useless_pages,
         database_file,
+        database_backend,
+        status_file: defaults::status_file(),
+        schema_file: defaults::schema_file(),
+        strictness,
+        quarantine_folder: None,
+        rules_file: None,
         backups,
+        archive,
         meilisearch,
+        sources: SourcesConfig {
+            google,
+            seeds,
+            feeds,
+            sitemaps,
+        },
+        schedules,
+        retry: RetryPolicy::default(),
     };
 
     let mut file = File::create("config.toml").expect("Unable to open config file");
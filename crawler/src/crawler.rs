@@ -1,67 +1,53 @@
-use crate::{config::*, google, gleam, meilisearch::*, database::*, backup::*};
-use std::{collections::HashMap, time::{Instant, Duration, SystemTime}, thread::sleep};
+use crate::{config::*, archive::{Snapshot, save_snapshot}, gleam::{self, ParseOptions}, store::*, sources::*, ratelimit::HostRateLimiter, fetch::*, daemon, retry::{RetryPolicy, Decision}};
+use std::{sync::Arc, time::{Duration, SystemTime}};
 use progress_bar::{color::*, progress_bar::ProgressBar};
-use url::Url;
-use format::{prelude::*, parsing::*};
-
-fn url_to_host(url: &str) -> String {
-    if let Ok(url) = &Url::parse(url) {
-        url.host_str().unwrap_or("unknown").to_string()
-    } else {
-        "unknown".to_string()
-    }
-}
-
-fn search_google_results(cooldown: u64) -> Vec<String> {
-    let mut progress_bar = ProgressBar::new(7);
-    progress_bar.set_action("Searching", Color::White, Style::Normal);
-    let mut results = Vec::new();
-    let mut page = 0;
-    loop {
-        progress_bar.set_action("Loading", Color::Blue, Style::Normal);
-        progress_bar.print_info("Getting", &format!("the results page {}", page), Color::Blue, Style::Normal);
-        let new_results = google::search(page).unwrap_or_default();
-        if !new_results.is_empty() {
-            for new_result in new_results {
-                results.push(new_result);
-            }
-            page += 1;
-            progress_bar.inc();
-            progress_bar.set_action("Sleeping", Color::Yellow, Style::Normal);
-            sleep(Duration::from_secs(cooldown));
-        } else {
-            break;
-        }
-    }
-    progress_bar.set_action("Finished", Color::Green, Style::Bold);
-    progress_bar.print_info("Finished", &format!("{} results found", results.len()), Color::Green, Style::Bold);
-    progress_bar.finalize();
-    println!();
-
-    results
-}
+use futures::stream::{self, StreamExt};
+use tokio::task;
+use format::giveaway::SearchResult;
+use serde_json::Value;
 
-fn load_results(results: Vec<String>, config: &Config, giveaways: &mut HashMap<String, SearchResult>, outdated_meilisearch: &mut Vec<String>, fast: bool) {
-    let cooldown = config.cooldown as u64;
+/// Load the pages and then the giveaways they link to, and return the number of new giveaways.
+/// Requests are made concurrently, but each host is limited by the rate limiter.
+/// The pages of a host are loaded one after the other, so that the ones waiting for the limiter never hold back other hosts.
+pub(crate) async fn load_results(results: Vec<String>, config: &Config, giveaways: &mut dyn GiveawayStore, fetcher: &Arc<dyn Fetcher>, limiter: &Arc<HostRateLimiter>, outdated_meilisearch: &mut Vec<String>, fast: bool) -> usize {
+    let concurrency = config.concurrency.max(1);
 
+    // Load the pages, unless they are already gleam.io links
     let mut progress_bar = ProgressBar::new(results.len());
-    let mut timeout_check = HashMap::new();
-    let mut last_gleam_request = Instant::now();
-    progress_bar.set_action("Loading", Color::White, Style::Normal);
-    for result in &results {
-        // Check the cooldown
-        if let Some(last_load_time) = timeout_check.get(&url_to_host(&result)) {
-            let time_since_last_load = Instant::now() - *last_load_time;
-            if time_since_last_load < Duration::from_secs(cooldown) {
-                let time_to_sleep = Duration::from_secs(cooldown) - time_since_last_load;
-                progress_bar.set_action("Sleeping", Color::Yellow, Style::Normal); 
-                sleep(time_to_sleep);
+    progress_bar.set_action("Loading", Color::Blue, Style::Normal);
+    let mut direct_links = Vec::new();
+    let mut hosts: Vec<(String, Vec<String>)> = Vec::new();
+    for result in results {
+        if let Some(id) = gleam::get_gleam_id(&result) {
+            let giveaway_url = format!("https://gleam.io/{}/-", id);
+            direct_links.push((result, Ok(vec![giveaway_url])));
+            continue;
+        }
+        let host = url_to_host(&result);
+        match hosts.iter_mut().find(|(known_host, _)| *known_host == host) {
+            Some((_, pages)) => pages.push(result),
+            None => hosts.push((host, vec![result])),
+        }
+    }
+    let loaded_pages = stream::iter(hosts).map(|(_host, pages)| {
+        let fetcher = Arc::clone(fetcher);
+        let limiter = Arc::clone(limiter);
+        async move {
+            let mut loaded = Vec::new();
+            for page in pages {
+                let url = page.clone();
+                let giveaway_urls = fetch_limited(&fetcher, &limiter, &page, move |fetcher| resolve(fetcher, &url)).await;
+                loaded.push((page, giveaway_urls));
             }
+            loaded
         }
-        
-        // Load the page
-        progress_bar.set_action("Loading", Color::Blue, Style::Normal);
-        let giveaway_urls = match resolve(result) {
+    }).buffer_unordered(concurrency);
+    let mut pages = stream::iter(direct_links).chain(loaded_pages.map(stream::iter).flatten());
+
+    let mut gleam_links: Vec<String> = Vec::new();
+    while let Some((result, giveaway_urls)) = pages.next().await {
+        progress_bar.inc();
+        let giveaway_urls = match giveaway_urls {
             Ok(urls) => urls,
             Err(e) => {
                 progress_bar.print_info("Error", &format!("when trying to load {}: {}", result, e), Color::Red, Style::Normal);
@@ -74,80 +60,115 @@ fn load_results(results: Vec<String>, config: &Config, giveaways: &mut HashMap<S
             progress_bar.print_info("Useless", &format!("page loaded: {}", result), Color::Yellow, Style::Normal);
         }
 
-        // Use the data
+        // Keep the valid urls we did not load before
         for gleam_link in giveaway_urls {
-            // Check if the url is valid and if we did not load this before
             if let Some(key) = gleam::get_gleam_id(&gleam_link) {
-                if giveaways.contains_key(key) {
+                if giveaways.contains(key) || giveaways.is_buried(key) || gleam_links.contains(&gleam_link) {
                     continue;
                 }
             } else {
                 continue;
             }
 
-            if !fast {
-                let time_since_last_load = Instant::now() - last_gleam_request;
-                if time_since_last_load < Duration::from_secs(cooldown) {
-                    let time_to_sleep = Duration::from_secs(cooldown) - time_since_last_load;
-                    progress_bar.set_action("Sleeping", Color::Yellow, Style::Normal);
-                    sleep(time_to_sleep);
-                }
-
-                progress_bar.set_action("Loading", Color::Blue, Style::Normal);
-                if let Ok(giveaway) = gleam::fetch(&gleam_link) {
-                    last_gleam_request = Instant::now();
-                    progress_bar.print_info("Found", &format!("{} {:>8} entries - {}", giveaway.get_url(), if let Some(entry_count) = giveaway.entry_count { entry_count.to_string() } else {String::from("unknow")}, giveaway.get_name()), Color::LightGreen, Style::Bold);
-                    outdated_meilisearch.push(giveaway.giveaway.campaign.key.clone());
-                    giveaways.insert(giveaway.giveaway.campaign.key.clone(), giveaway);
-                    
-                }
-            } else {
+            if fast {
                 progress_bar.print_info("Found", &gleam_link, Color::LightGreen, Style::Bold);
             }
+            gleam_links.push(gleam_link);
         }
-        
+    }
+    progress_bar.set_action("Finished", Color::Green, Style::Bold);
+    progress_bar.finalize();
+    println!();
+
+    if fast {
+        println!("{} giveaways found", gleam_links.len());
+        return gleam_links.len();
+    }
+
+    // Load the giveaways
+    let mut progress_bar = ProgressBar::new(gleam_links.len());
+    progress_bar.set_action("Fetching", Color::Blue, Style::Normal);
+    let mut fetched = stream::iter(gleam_links)
+        .map(|gleam_link| fetch_with_retries(gleam_link, Arc::clone(fetcher), Arc::clone(limiter), config.retry, config.parse_options()))
+        .buffer_unordered(concurrency);
+
+    let mut found = 0;
+    while let Some(giveaway) = fetched.next().await {
         progress_bar.inc();
-        timeout_check.insert(url_to_host(result), Instant::now());
+        if let Ok((giveaway, snapshot)) = giveaway {
+            found += 1;
+            if let Some(archive_config) = &config.archive {
+                save_snapshot(archive_config, &Snapshot::new(&giveaway, snapshot));
+            }
+            progress_bar.print_info("Found", &format!("{} {:>8} entries - {}", giveaway.get_url(), if let Some(entry_count) = giveaway.entry_count { entry_count.to_string() } else {String::from("unknow")}, giveaway.get_name()), Color::LightGreen, Style::Bold);
+            outdated_meilisearch.push(giveaway.giveaway.campaign.key.clone());
+            giveaways.upsert(giveaway);
+        }
     }
     progress_bar.set_action("Finished", Color::Green, Style::Bold);
     progress_bar.print_info("Finished", &format!("{} giveaways found", giveaways.len()), Color::Green, Style::Bold);
     progress_bar.finalize();
     println!();
+
+    found
+}
+
+/// Load a campaign, retrying according to the policy.
+/// When it fails, the last error is returned with the decision of the policy.
+async fn fetch_with_retries(url: String, fetcher: Arc<dyn Fetcher>, limiter: Arc<HostRateLimiter>, policy: RetryPolicy, options: ParseOptions) -> Result<(SearchResult, Value), (gleam::Error, Decision)> {
+    let mut attempt = 1;
+    loop {
+        limiter.acquire("gleam.io").await;
+        let (fetcher, url, options) = (Arc::clone(&fetcher), url.clone(), options.clone());
+        let error = match task::spawn_blocking(move || gleam::fetch(fetcher.as_ref(), &url, &options)).await.expect("Failed to join the fetching task") {
+            Ok(giveaway) => return Ok(giveaway),
+            Err(error) => error,
+        };
+
+        match policy.decide(&error, attempt) {
+            // The whole host is overloaded
+            Decision::Retry(delay) if matches!(error, gleam::Error::RateLimited { .. } | gleam::Error::ServerFailure(_)) => limiter.pause("gleam.io", delay),
+            Decision::Retry(delay) => tokio::time::delay_for(delay).await,
+            decision => return Err((error, decision)),
+        }
+        attempt += 1;
+    }
 }
 
-fn update_giveaways(to_update: Vec<String>, giveaways: &mut HashMap<String, SearchResult>, outdated_meilisearch: &mut Vec<String>, cooldown: u64) {
+pub(crate) async fn update_giveaways(to_update: Vec<String>, giveaways: &mut dyn GiveawayStore, fetcher: &Arc<dyn Fetcher>, limiter: &Arc<HostRateLimiter>, outdated_meilisearch: &mut Vec<String>, config: &Config) {
+    let to_update: Vec<SearchResult> = to_update.iter().filter_map(|key| giveaways.get(key)).collect();
     let len = to_update.len();
     let mut progress_bar = ProgressBar::new(len);
-    for key in to_update {
-        progress_bar.set_action("Updating", Color::Blue, Style::Normal);
-        let mut old_giveaway = giveaways.remove(&key).unwrap();
+    progress_bar.set_action("Updating", Color::Blue, Style::Normal);
+
+    let mut updates = stream::iter(to_update).map(|old_giveaway| {
+        let result = fetch_with_retries(old_giveaway.get_url(), Arc::clone(fetcher), Arc::clone(limiter), config.retry, config.parse_options());
+        async move { (old_giveaway, result.await) }
+    }).buffer_unordered(config.concurrency.max(1));
+
+    while let Some((mut old_giveaway, result)) = updates.next().await {
+        progress_bar.inc();
+        let key = old_giveaway.giveaway.campaign.key.clone();
         outdated_meilisearch.push(key.clone());
 
-        match gleam::fetch(&old_giveaway.get_url()) {
-            Ok(updated) => {
-                let giveaway = old_giveaway + updated;
-                giveaways.insert(key, giveaway);
+        match result {
+            Ok((updated, snapshot)) => {
+                if let Some(archive_config) = &config.archive {
+                    save_snapshot(archive_config, &Snapshot::new(&updated, snapshot));
+                }
+                giveaways.upsert(updated);
             },
-            Err(gleam::Error::ParseError(ParseError::GiveawayJsonNotFound)) => {
-                progress_bar.print_info("Missing", &format!("giveaway {} -> removing", old_giveaway.get_url()), Color::Red, Style::Blink);
+            Err((e, Decision::Remove)) => {
+                progress_bar.print_info("Missing", &format!("giveaway {} ({}) -> removing", old_giveaway.get_url(), e), Color::Red, Style::Blink);
+                giveaways.bury(&key, &e.to_string(), SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs());
             }
-            Err(gleam::Error::ParseError(e)) => {
-                progress_bar.print_info("Invalid", &format!("giveaway {}: {:?}", old_giveaway.get_url(), e), Color::Red, Style::Blink);
-                old_giveaway.last_updated = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-                giveaways.insert(key, old_giveaway);
-            }
-            Err(gleam::Error::NetworkError(_e)) => {
-                progress_bar.print_info("Timeout", "Failed to load giveaway (giveaway has not been updated)", Color::Yellow, Style::Bold);
-                giveaways.insert(key, old_giveaway);
-                sleep(Duration::from_secs(10));
-            }
-            Err(gleam::Error::InvalidGleamUrl) => {
-                progress_bar.print_info("Invalid", &format!("url {} (this code is almost unreachable)", old_giveaway.get_url()), Color::Red, Style::Blink);
+            Err((e, _decision)) => {
+                progress_bar.print_info("Failed", &format!("to update {}: {} (giveaway has not been updated)", old_giveaway.get_url(), e), Color::Yellow, Style::Bold);
+                old_giveaway.failed_at = Some(SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs());
+                old_giveaway.failures += 1;
+                giveaways.upsert(old_giveaway);
             }
         }
-        progress_bar.set_action("Sleeping", Color::Yellow, Style::Normal);
-        progress_bar.inc();
-        sleep(Duration::from_secs(cooldown));
     }
     progress_bar.print_info("Finished", &format!("{} giveaways updated", len), Color::Green, Style::Bold);
     progress_bar.set_action("Finished", Color::Green, Style::Bold);
@@ -155,71 +176,20 @@ fn update_giveaways(to_update: Vec<String>, giveaways: &mut HashMap<String, Sear
     println!();
 }
 
+/// Run the crawler as a daemon, or load the results of a single discovery if `fast` is set.
 pub async fn launch(config: Config, fast: bool) {
     std::env::set_var("MINREQ_TIMEOUT", config.timeout.to_string());
-    let cooldown = config.cooldown as u64;
-    let mut run_number = 0;
-
-    if matches!(config.meilisearch.as_ref().map(|m| m.init_on_launch), Some(true)) {
-        println!("Initializing the MeiliSearch index...");
-        init_meilisearch(&config).await;
-        println!("Done!");
+    if !fast {
+        daemon::run(config).await;
+        return;
     }
-    
-    loop {
-        let mut giveaways: HashMap<String, SearchResult> = HashMap::new();
-        let mut outdated_meilisearch = Vec::new();
-        let start = Instant::now();
-
-        // Search results on google
-        let results = search_google_results(cooldown);
-
-        // Load the results
-        load_results(results, &config, &mut giveaways, &mut outdated_meilisearch, fast);
 
-        if fast { break; }
-
-        // Read the database
-        read_database(&mut giveaways, &config);
-
-        // Select the oldest giveaways to update them
-        let mut to_update = Vec::new();
-        if config.update > 0 {
-            let mut giveaways = giveaways.iter().map(|(_i, g)| g).collect::<Vec<&SearchResult>>();
-            giveaways.sort_by_key(|g| g.last_updated);
-            for idx in 0..config.update {
-                if let Some(giveaway) = giveaways.get(idx) {
-                    to_update.push(giveaway.giveaway.campaign.key.clone())
-                }
-            }
-        }
-        
-        // Update the oldest giveaways
-        update_giveaways(to_update, &mut giveaways, &mut outdated_meilisearch, cooldown);
-
-        // Save the database
-        save_database(&giveaways, &config);
-
-        // Update meilisearch index
-        if let Err(e) = update_meilisearch(giveaways, &config, outdated_meilisearch).await {
-            eprintln!("Error: Failed to update meilisearch index: {}", e);
-        };
-
-        if let Some(backup_config) = &config.backups {
-            if run_number%backup_config.interval == 0 {
-                backup(&config.database_file, &backup_config);
-            }
-        }
-
-        if !fast {
-            let time_elapsed = Instant::now().duration_since(start);
-            let time_to_sleep = Duration::from_secs(3540) - time_elapsed;
-            run_number += 1;
-            sleep(time_to_sleep);
-        } else {
-            break;
-        }
-    }
+    let fetcher: Arc<dyn Fetcher> = Arc::new(MinreqFetcher);
+    let limiter = Arc::new(HostRateLimiter::new(Duration::from_secs(config.cooldown as u64), 1));
+    let mut sources = build_sources(&config, &fetcher, &limiter);
+    let mut giveaways = open_store(&config);
+    let results = discover(&mut sources).await;
+    load_results(results, &config, giveaways.as_mut(), &fetcher, &limiter, &mut Vec::new(), true).await;
 }
 
 /// put an url+noise, get url (without http://domain.something/)
@@ -234,30 +204,30 @@ fn get_url(url: &str) -> &str {
     &url[..i]
 }
 
-pub fn resolve(url: &str) -> Result<Vec<String>, minreq::Error> {
+pub fn resolve(fetcher: &dyn Fetcher, url: &str) -> Result<Vec<String>, minreq::Error> {
     use string_tools::*;
 
-    let response = match minreq::get(url)
-        .with_header("Accept", "text/html,text/plain")
-        .with_header(
-            "User-Agent",
-            "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:78.0) Gecko/20100101 Firefox/78.0",
-        )
-        .with_header(
-            "Host",
-            get_all_between(url, "://", "/"),
-        )
-        .send()
-    {
+    let response = match fetcher.get(url, &[
+        ("Accept", "text/html,text/plain"),
+        ("User-Agent", "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:78.0) Gecko/20100101 Firefox/78.0"),
+        ("Host", get_all_between(url, "://", "/")),
+    ]) {
         Ok(response) => response,
         Err(e) => return Err(e),
     };
 
-    let mut body = match response.as_str() {
+    let body = match response.as_str() {
         Ok(body) => body,
         Err(e) => return Err(e),
     };
 
+    Ok(extract_gleam_links(body))
+}
+
+/// Extract the normalized gleam.io links contained in a text.
+pub fn extract_gleam_links(mut body: &str) -> Vec<String> {
+    use string_tools::*;
+
     let mut rep = Vec::new();
     while get_all_after(&body, "https://gleam.io/") != "" {
         let url = get_url(get_all_after(&body, "https://gleam.io/"));
@@ -279,16 +249,73 @@ pub fn resolve(url: &str) -> Result<Vec<String>, minreq::Error> {
             final_rep.push(format!("https://gleam.io/{}/-", id));
         }
     }
-    Ok(final_rep)
+    final_rep
 }
 
 #[cfg(test)]
 mod test {
-    use super::resolve;
+    use super::*;
+    use crate::{google::GoogleSource, scheduler, store::tests::giveaway};
+
+    #[test]
+    fn extracting() {
+        assert_eq!(
+            extract_gleam_links("<a href=\"https://gleam.io/7qHd6/sorteo\">win</a> https://gleam.io/competitions/lSq1Q-s and https://gleam.io/7qHd6/sorteo again"),
+            vec!["https://gleam.io/7qHd6/-", "https://gleam.io/lSq1Q/-"]
+        );
+    }
 
     #[test]
     fn resolving() {
-        assert_eq!(resolve("https://www.youtube.com/watch?v=-DS1qgHjoJY").unwrap().len(), 1);
-        assert_eq!(resolve("https://news.nestia.com/detail/Oculus-Quest-2---Infinite-Free-Games!/5222508").unwrap().len(), 1);
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        assert_eq!(resolve(&fetcher, "https://example.com/win-a-gpu").unwrap(), vec!["https://gleam.io/7qHd6/-", "https://gleam.io/zzzzz/-"]);
+        assert!(resolve(&fetcher, "https://example.org/nothing").unwrap().is_empty());
+        assert!(resolve(&fetcher, "https://example.net/unknown").is_err());
+    }
+
+    #[tokio::test]
+    async fn crawling() {
+        let config: Config = toml::from_str("[retry]\nbase_delay = 0").unwrap();
+        let fetcher: Arc<dyn Fetcher> = Arc::new(FixtureFetcher::new("test_data/pages"));
+        let limiter = Arc::new(HostRateLimiter::new(Duration::from_secs(0), 1));
+        let mut giveaways = MemoryStore::new();
+        let mut outdated_meilisearch = Vec::new();
+
+        // Discover and load new giveaways
+        let mut sources: Vec<Box<dyn Source>> = vec![Box::new(GoogleSource::new(Arc::clone(&fetcher), Arc::clone(&limiter)))];
+        let results = discover(&mut sources).await;
+        assert_eq!(results.len(), 2);
+        let found = load_results(results, &config, &mut giveaways, &fetcher, &limiter, &mut outdated_meilisearch, false).await;
+        assert_eq!(found, 1);
+        assert_eq!(giveaways.len(), 1);
+        assert_eq!(giveaways.get("7qHd6").unwrap().entry_count, Some(1337));
+        assert_eq!(outdated_meilisearch, vec!["7qHd6"]);
+
+        // Update them, removing the ones which disappeared
+        giveaways.upsert(giveaway("zzzzz", 0, 1000));
+        giveaways.upsert(giveaway("gone1", 0, 1000));
+        giveaways.upsert(giveaway("errr1", 0, 1000));
+        let in_an_hour = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() + 3600;
+        let to_update = scheduler::select(&giveaways, 4, in_an_hour);
+        assert_eq!(to_update.len(), 4);
+        update_giveaways(to_update, &mut giveaways, &fetcher, &limiter, &mut outdated_meilisearch, &config).await;
+        assert_eq!(outdated_meilisearch.len(), 5);
+
+        // Campaigns which are not found are removed, the others are kept but marked as failed
+        assert_eq!(giveaways.len(), 3);
+        assert!(!giveaways.contains("gone1"));
+        assert!(giveaways.is_buried("gone1"));
+        for key in ["zzzzz", "errr1"].iter() {
+            let failed = giveaways.get(key).unwrap();
+            assert_eq!(failed.last_updated, 0);
+            assert!(failed.failed_at.is_some());
+            assert_eq!(failed.failures, 1);
+        }
+
+        // Buried campaigns are not discovered again
+        let mut outdated_meilisearch = Vec::new();
+        let found = load_results(vec![String::from("https://gleam.io/gone1/-")], &config, &mut giveaways, &fetcher, &limiter, &mut outdated_meilisearch, false).await;
+        assert_eq!(found, 0);
+        assert!(outdated_meilisearch.is_empty());
     }
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+/// We never look for the next run further than this (a bit more than 4 years, to find leap days).
+const MAX_SEARCH_MINUTES: u64 = 5 * 366 * 24 * 60;
+
+/// A set of allowed values for a cron field, stored as a bitmask.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    allowed: u64,
+    restricted: bool,
+}
+
+impl Field {
+    fn parse(field: &str, min: u64, max: u64) -> Result<Field, String> {
+        let mut allowed = 0;
+        for part in field.split(',') {
+            let (range, step) = match part.find('/') {
+                Some(idx) => {
+                    let step = part[idx + 1..].parse::<u64>().map_err(|_| format!("invalid step in {:?}", part))?;
+                    if step == 0 {
+                        return Err(format!("invalid step in {:?}", part));
+                    }
+                    (&part[..idx], step)
+                }
+                None => (part, 1),
+            };
+
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some(idx) = range.find('-') {
+                let start = range[..idx].parse::<u64>().map_err(|_| format!("invalid range {:?}", range))?;
+                let end = range[idx + 1..].parse::<u64>().map_err(|_| format!("invalid range {:?}", range))?;
+                (start, end)
+            } else {
+                let value = range.parse::<u64>().map_err(|_| format!("invalid value {:?}", range))?;
+                // `5/15` means from 5 to the end, every 15
+                (value, if step > 1 { max } else { value })
+            };
+
+            if start < min || end > max || start > end {
+                return Err(format!("{:?} is out of range ({}-{})", part, min, max));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                allowed |= 1 << value;
+            }
+        }
+
+        Ok(Field {
+            allowed,
+            restricted: field != "*",
+        })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.allowed & (1 << value) != 0
+    }
+}
+
+/// A standard cron expression: `minute hour day-of-month month day-of-week`.
+/// Times are in UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let fields = expression.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields in {:?}, found {}", expression, fields.len()));
+        }
+
+        // Sunday can be written 0 or 7
+        let mut days_of_week = Field::parse(fields[4], 0, 7)?;
+        if days_of_week.matches(7) {
+            days_of_week.allowed |= 1;
+        }
+
+        Ok(CronSchedule {
+            minutes: Field::parse(fields[0], 0, 59)?,
+            hours: Field::parse(fields[1], 0, 23)?,
+            days_of_month: Field::parse(fields[2], 1, 31)?,
+            months: Field::parse(fields[3], 1, 12)?,
+            days_of_week,
+        })
+    }
+
+    fn matches(&self, date: &NaiveDateTime) -> bool {
+        // When both day fields are restricted, matching any of them is enough
+        let day_of_month = self.days_of_month.matches(date.day());
+        let day_of_week = self.days_of_week.matches(date.weekday().num_days_from_sunday());
+        let day = match (self.days_of_month.restricted, self.days_of_week.restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day && self.minutes.matches(date.minute()) && self.hours.matches(date.hour()) && self.months.matches(date.month())
+    }
+
+    /// Get the first time matching the expression strictly after `timestamp`.
+    pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+        let first_minute = timestamp / 60 + 1;
+        (first_minute..first_minute + MAX_SEARCH_MINUTES)
+            .map(|minute| minute * 60)
+            .find(|timestamp| self.matches(&NaiveDateTime::from_timestamp(*timestamp as i64, 0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn timestamp(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> u64 {
+        NaiveDate::from_ymd(year, month, day).and_hms(hour, minute, 0).timestamp() as u64
+    }
+
+    #[test]
+    fn parsing() {
+        assert!(CronSchedule::parse("* * * * *").is_ok());
+        assert!(CronSchedule::parse("*/15 0-6,22,23 1 */2 1-5").is_ok());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-2 * * * *").is_err());
+        assert!(CronSchedule::parse("a * * * *").is_err());
+    }
+
+    #[test]
+    fn next_run() {
+        let now = timestamp(2020, 10, 14, 12, 34);
+
+        let hourly = CronSchedule::parse("0 * * * *").unwrap();
+        assert_eq!(hourly.next_after(now), Some(timestamp(2020, 10, 14, 13, 0)));
+        assert_eq!(hourly.next_after(timestamp(2020, 10, 14, 13, 0)), Some(timestamp(2020, 10, 14, 14, 0)));
+
+        let quarters = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(quarters.next_after(now), Some(timestamp(2020, 10, 14, 12, 45)));
+
+        let nightly = CronSchedule::parse("30 3 * * *").unwrap();
+        assert_eq!(nightly.next_after(now), Some(timestamp(2020, 10, 15, 3, 30)));
+
+        // The 14th of October 2020 is a Wednesday
+        let sundays = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(sundays.next_after(now), Some(timestamp(2020, 10, 18, 0, 0)));
+
+        let first_or_monday = CronSchedule::parse("0 0 1 * 1").unwrap();
+        assert_eq!(first_or_monday.next_after(now), Some(timestamp(2020, 10, 19, 0, 0)));
+        assert_eq!(first_or_monday.next_after(timestamp(2020, 10, 26, 1, 0)), Some(timestamp(2020, 11, 1, 0, 0)));
+
+        let leap_days = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap_days.next_after(now), Some(timestamp(2024, 2, 29, 0, 0)));
+
+        let never = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(now), None);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{config::*, crawler::*, cron::CronSchedule, fetch::*, meilisearch::*, ratelimit::HostRateLimiter, scheduler, sources::*, store::*, backup::backup};
+use serde::{Serialize, Deserialize};
+use std::{collections::BTreeMap, fs::File, io::prelude::*, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// When a job has to run.
+#[derive(Debug, PartialEq)]
+pub enum Schedule {
+    Cron(CronSchedule),
+    /// A number of seconds between two runs
+    Every(u64),
+}
+
+impl Schedule {
+    pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+        match self {
+            Schedule::Cron(cron) => cron.next_after(timestamp),
+            Schedule::Every(interval) => Some(timestamp + interval),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Job {
+    Discovery,
+    Updates,
+    Meilisearch,
+    Backups,
+}
+
+impl Job {
+    fn name(self) -> &'static str {
+        match self {
+            Job::Discovery => "discovery",
+            Job::Updates => "updates",
+            Job::Meilisearch => "meilisearch",
+            Job::Backups => "backups",
+        }
+    }
+}
+
+/// Get the jobs enabled in the configuration file, in the order they run when they are due at the same time.
+pub fn jobs(config: &Config) -> Result<Vec<(Job, Schedule)>, String> {
+    fn cron(job: &str, expression: &str) -> Result<Schedule, String> {
+        CronSchedule::parse(expression)
+            .map(Schedule::Cron)
+            .map_err(|e| format!("invalid schedule for {}: {}", job, e))
+    }
+
+    let mut jobs = vec![
+        (Job::Discovery, cron("discovery", &config.schedules.discovery)?),
+        (Job::Updates, cron("updates", &config.schedules.updates)?),
+    ];
+    if config.meilisearch.is_some() {
+        jobs.push((Job::Meilisearch, cron("meilisearch", &config.schedules.meilisearch)?));
+    }
+    if let Some(backups) = &config.backups {
+        let schedule = match &config.schedules.backups {
+            Some(expression) => cron("backups", expression)?,
+            None => Schedule::Every(backups.interval.max(1) as u64 * 3600),
+        };
+        jobs.push((Job::Backups, schedule));
+    }
+    Ok(jobs)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub last_run: Option<u64>,
+    /// In seconds
+    pub duration: Option<u64>,
+    pub next_run: Option<u64>,
+    pub result: Option<String>,
+}
+
+/// The content of the status file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub started_at: u64,
+    pub jobs: BTreeMap<String, JobStatus>,
+}
+
+impl Status {
+    fn save(&self, path: &str) {
+        let data = serde_json::to_string_pretty(self).expect("Can't serialize status");
+        match File::create(path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(data.as_bytes()) {
+                    eprintln!("Failed to write the status file {}: {}", path, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to open the status file {}: {}", path, e),
+        }
+    }
+}
+
+/// Resolves when the process is asked to stop (SIGTERM or Ctrl-C).
+struct Shutdown {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Shutdown {
+    fn new() -> Shutdown {
+        Shutdown {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).expect("Failed to listen to SIGTERM"),
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = self.terminate.recv() => (),
+            _ = tokio::signal::ctrl_c() => (),
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Everything the jobs share.
+struct Crawler {
+    config: Config,
+    fetcher: Arc<dyn Fetcher>,
+    limiter: Arc<HostRateLimiter>,
+    sources: Vec<Box<dyn Source>>,
+    giveaways: Box<dyn GiveawayStore>,
+    outdated_meilisearch: Vec<String>,
+}
+
+impl Crawler {
+    /// Run a job and describe what it did.
+    async fn run(&mut self, job: Job) -> String {
+        match job {
+            Job::Discovery => {
+                let results = discover(&mut self.sources).await;
+                let pages = results.len();
+                let found = load_results(results, &self.config, self.giveaways.as_mut(), &self.fetcher, &self.limiter, &mut self.outdated_meilisearch, false).await;
+                self.giveaways.flush();
+                format!("{} pages loaded, {} new giveaways", pages, found)
+            }
+            Job::Updates => {
+                let to_update = scheduler::select(self.giveaways.as_ref(), self.config.update, now());
+                let updated = to_update.len();
+                update_giveaways(to_update, self.giveaways.as_mut(), &self.fetcher, &self.limiter, &mut self.outdated_meilisearch, &self.config).await;
+                self.giveaways.flush();
+                format!("{} giveaways updated", updated)
+            }
+            Job::Meilisearch => {
+                let outdated = std::mem::take(&mut self.outdated_meilisearch);
+                let count = outdated.len();
+                match update_meilisearch(self.giveaways.as_ref(), &self.config, outdated.clone()).await {
+                    Ok(_) => format!("{} documents updated", count),
+                    Err(e) => {
+                        eprintln!("Error: Failed to update meilisearch index: {}", e);
+                        self.outdated_meilisearch.extend(outdated);
+                        format!("failed: {}", e)
+                    }
+                }
+            }
+            Job::Backups => {
+                if let Some(backup_config) = &self.config.backups {
+                    backup(self.giveaways.as_ref(), backup_config);
+                }
+                format!("{} giveaways saved", self.giveaways.len())
+            }
+        }
+    }
+}
+
+/// Run the jobs on their schedules until the process is asked to stop.
+pub async fn run(config: Config) {
+    let jobs = match jobs(&config) {
+        Ok(jobs) => jobs,
+        Err(e) => panic!("Your configuration file is not valid: {}", e),
+    };
+    let fetcher: Arc<dyn Fetcher> = Arc::new(MinreqFetcher);
+    let limiter = Arc::new(HostRateLimiter::new(Duration::from_secs(config.cooldown as u64), 1));
+    let mut crawler = Crawler {
+        sources: build_sources(&config, &fetcher, &limiter),
+        limiter,
+        giveaways: open_store(&config),
+        outdated_meilisearch: Vec::new(),
+        fetcher,
+        config,
+    };
+
+    if matches!(crawler.config.meilisearch.as_ref().map(|m| m.init_on_launch), Some(true)) {
+        println!("Initializing the MeiliSearch index...");
+        init_meilisearch(crawler.giveaways.as_ref(), &crawler.config).await;
+        println!("Done!");
+    }
+
+    // Every job runs once on startup
+    let start = now();
+    let mut next_runs: Vec<Option<u64>> = jobs.iter().map(|_| Some(start)).collect();
+    let mut status = Status {
+        started_at: start,
+        ..Status::default()
+    };
+    let mut shutdown = Shutdown::new();
+
+    'daemon: loop {
+        let next_run = match next_runs.iter().flatten().min() {
+            Some(next_run) => *next_run,
+            None => {
+                println!("No job will ever run again.");
+                shutdown.recv().await;
+                break;
+            }
+        };
+
+        let now = now();
+        if next_run > now {
+            tokio::select! {
+                _ = tokio::time::delay_for(Duration::from_secs(next_run - now)) => continue,
+                _ = shutdown.recv() => break,
+            }
+        }
+
+        for (idx, (job, schedule)) in jobs.iter().enumerate() {
+            if !matches!(next_runs[idx], Some(next_run) if next_run <= now) {
+                continue;
+            }
+
+            let started_at = self::now();
+            let result = tokio::select! {
+                result = crawler.run(*job) => Some(result),
+                _ = shutdown.recv() => None,
+            };
+            let result = match result {
+                Some(result) => result,
+                None => break 'daemon,
+            };
+            let finished_at = self::now();
+
+            next_runs[idx] = schedule.next_after(finished_at);
+            status.jobs.insert(job.name().to_string(), JobStatus {
+                last_run: Some(started_at),
+                duration: Some(finished_at - started_at),
+                next_run: next_runs[idx],
+                result: Some(result),
+            });
+            status.save(&crawler.config.status_file);
+        }
+    }
+
+    println!("Stopping the crawler...");
+    crawler.giveaways.flush();
+    status.save(&crawler.config.status_file);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_jobs() {
+        let config: Config = toml::from_str("").unwrap();
+        let enabled = jobs(&config).unwrap().into_iter().map(|(job, _)| job).collect::<Vec<Job>>();
+        assert_eq!(enabled, vec![Job::Discovery, Job::Updates]);
+
+        let config: Config = toml::from_str("
+            [backups]
+            interval = 6
+            folder = \"backups\"
+            max = 10
+
+            [meilisearch]
+            host = \"http://localhost:7700\"
+            index = \"giveaways\"
+            key = \"key\"
+        ").unwrap();
+        let jobs = jobs(&config).unwrap();
+        assert_eq!(jobs.len(), 4);
+        assert_eq!(jobs[3], (Job::Backups, Schedule::Every(6 * 3600)));
+        assert_eq!(jobs[0].1.next_after(3599), Some(3600));
+
+        let config: Config = toml::from_str("
+            [schedules]
+            updates = \"every hour\"
+        ").unwrap();
+        assert!(self::jobs(&config).is_err());
+    }
+}
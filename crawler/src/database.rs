@@ -1,33 +1,328 @@
-use std::{collections::HashMap, fs::File, io::prelude::*};
+use std::{fs::{File, rename}, io::{prelude::*, ErrorKind}, path::Path};
 use format::giveaway::SearchResult;
-use crate::config::Config;
-
-pub(crate) fn read_database(giveaways: &mut HashMap<String, SearchResult>, config: &Config) {
-    match File::open(&config.database_file) {
-        Ok(mut file) => {
-            let mut content = String::new();
-            file.read_to_string(&mut content).expect("Failed to read database");
-            let saved_giveaways = serde_json::from_str::<Vec<SearchResult>>(&content).expect("Failed to parse database");
-            for saved_giveaway in saved_giveaways {
-                match giveaways.remove(&saved_giveaway.giveaway.campaign.key) {
-                    Some(old_giveaway) => {
-                        let giveaway = saved_giveaway + old_giveaway;
-                        giveaways.insert(giveaway.giveaway.campaign.key.clone(), giveaway);
-                    },
-                    None => {
-                        giveaways.insert(saved_giveaway.giveaway.campaign.key.clone(), saved_giveaway);
-                    }
-                }
+use serde::{de::DeserializeOwned, Serialize};
+use crate::{config::{Config, DatabaseBackend, BackupConfig}, backup::backups_newest_first, sqlite::SqliteStore, store::*};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Corrupted(serde_json::Error),
+    /// The database is unreadable and backups are not configured
+    NoBackups,
+    /// The database is unreadable and none of the backups in this folder can be read
+    NoUsableBackup(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Corrupted(e) => write!(f, "corrupted data: {}", e),
+            Error::NoBackups => write!(f, "the database is unreadable and backups are not configured"),
+            Error::NoUsableBackup(folder) => write!(f, "the database is unreadable and there is no usable backup in {}", folder),
+        }
+    }
+}
+
+pub(crate) fn read_json<T: DeserializeOwned>(path: &str) -> Result<Vec<T>, Error> {
+    let mut file = File::open(path).map_err(Error::Io)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).map_err(Error::Io)?;
+    serde_json::from_str::<Vec<T>>(&content).map_err(Error::Corrupted)
+}
+
+/// Write the items to a temporary file and then replace the destination file.
+/// A crash never leaves a partially written file at `path`.
+pub(crate) fn save_json<'a, T: Serialize + 'a>(items: impl Iterator<Item = &'a T>, path: &str) {
+    let temporary_path = format!("{}.tmp", path);
+    let mut file = File::create(&temporary_path).expect("Can't open temporary database file");
+    let data = serde_json::to_string(&items.collect::<Vec<&T>>()).expect("Can't serialize database");
+    file.write_all(data.as_bytes()).expect("Can't write data to database");
+    file.sync_all().expect("Can't write data to disk");
+    rename(&temporary_path, path).expect("Can't replace database file");
+    sync_folder(path);
+}
+
+/// Write the folder containing `path` to disk, so that a file renamed in it is still there after a crash.
+#[cfg(unix)]
+fn sync_folder(path: &str) {
+    let folder = Path::new(path).parent().filter(|folder| !folder.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    File::open(folder).and_then(|folder| folder.sync_all()).expect("Can't write database folder to disk");
+}
+
+/// Folders can't be opened as files on other platforms.
+#[cfg(not(unix))]
+fn sync_folder(_path: &str) {}
+
+/// Move an unreadable database file so that it does not get overwritten.
+pub(crate) fn set_aside(path: &str) {
+    let corrupted_path = format!("{}.corrupted", path);
+    match rename(path, &corrupted_path) {
+        Ok(()) => eprintln!("The unreadable database has been moved to {}", corrupted_path),
+        Err(e) => eprintln!("Failed to move the unreadable database {}: {}", path, e),
+    }
+}
+
+/// Read the most recent backup which is not corrupted, and the tombstones saved with it.
+pub(crate) fn recover_from_backups(backups: Option<&BackupConfig>) -> Result<(Vec<SearchResult>, Vec<Tombstone>), Error> {
+    let backups = backups.ok_or(Error::NoBackups)?;
+
+    for path in backups_newest_first(&backups.folder) {
+        match read_json(&path.to_string_lossy()) {
+            Ok(giveaways) => {
+                let tombstones = read_tombstones(&path.to_string_lossy());
+                eprintln!("Recovered {} giveaways and {} tombstones from the backup {:?}", giveaways.len(), tombstones.len(), path);
+                return Ok((giveaways, tombstones));
             }
-        },
+            Err(e) => eprintln!("Unable to recover from the backup {:?}: {}", path, e),
+        }
+    }
+
+    Err(Error::NoUsableBackup(backups.folder.clone()))
+}
+
+/// Tombstones are saved next to the database: `giveaways.json` -> `giveaways.tombstones.json`.
+pub(crate) fn tombstones_path(path: &str) -> String {
+    format!("{}.tombstones.json", path.strip_suffix(".json").unwrap_or(path))
+}
+
+/// Read the tombstones saved next to a JSON database, if there are any.
+fn read_tombstones(path: &str) -> Vec<Tombstone> {
+    let path = tombstones_path(path);
+    match read_json(&path) {
+        Ok(tombstones) => tombstones,
+        Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(Error::Io(e)) => panic!("Failed to read tombstones: {}", e),
         Err(e) => {
-            eprintln!("Can't open save file: {}", e);
+            eprintln!("The tombstones file {} is unreadable: {}", path, e);
+            set_aside(&path);
+            Vec::new()
         }
     }
 }
 
-pub(crate) fn save_database(giveaways: &HashMap<String, SearchResult>, config: &Config) {
-    let mut file = File::create(&config.database_file).expect("Can't open database file");
-    let data = serde_json::to_string(&giveaways.values().collect::<Vec<&SearchResult>>()).expect("Can't serialize database");
-    file.write_all(data.as_bytes()).expect("Can't write data to database");
-}
\ No newline at end of file
+/// A store loading a JSON file in memory, and rewriting it entirely when flushed.
+pub struct JsonStore {
+    path: String,
+    giveaways: MemoryStore,
+    modified: bool,
+    tombstones_modified: bool,
+}
+
+impl JsonStore {
+    /// Load the database, or the latest backup if the database is unreadable.
+    /// Fails if the database is unreadable and can't be recovered.
+    pub fn open(path: &str, backups: Option<&BackupConfig>) -> Result<JsonStore, Error> {
+        // Recovered giveaways must be written to a new database file
+        let (saved_giveaways, recovered_tombstones, modified) = match read_json(path) {
+            Ok(giveaways) => (giveaways, Vec::new(), false),
+            Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                eprintln!("Can't open save file: {}", e);
+                (Vec::new(), Vec::new(), false)
+            }
+            Err(Error::Io(e)) => panic!("Failed to read database: {}", e),
+            Err(e) => {
+                eprintln!("The database {} is unreadable: {}", path, e);
+                // The database stays in place until it can be replaced, so that the next start fails too
+                let (giveaways, tombstones) = recover_from_backups(backups)?;
+                set_aside(path);
+                (giveaways, tombstones, true)
+            }
+        };
+
+        let mut giveaways = MemoryStore::new();
+        for giveaway in saved_giveaways {
+            giveaways.upsert(giveaway);
+        }
+        // The tombstones file of the database is more recent than the backup, so it is read last
+        let tombstones_modified = !recovered_tombstones.is_empty();
+        for tombstone in recovered_tombstones.into_iter().chain(read_tombstones(path)) {
+            giveaways.insert_tombstone(tombstone);
+        }
+
+        Ok(JsonStore {
+            path: path.to_string(),
+            giveaways,
+            modified,
+            tombstones_modified,
+        })
+    }
+}
+
+impl GiveawayStore for JsonStore {
+    fn get(&self, key: &str) -> Option<SearchResult> {
+        self.giveaways.get(key)
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.giveaways.contains(key)
+    }
+
+    fn upsert(&mut self, giveaway: SearchResult) {
+        self.modified = true;
+        self.giveaways.upsert(giveaway)
+    }
+
+    fn delete(&mut self, key: &str) -> bool {
+        self.modified = true;
+        self.giveaways.delete(key)
+    }
+
+    fn len(&self) -> usize {
+        self.giveaways.len()
+    }
+
+    fn iter_all(&self) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        self.giveaways.iter_all()
+    }
+
+    fn iter_running(&self, now: u64) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        self.giveaways.iter_running(now)
+    }
+
+    fn insert_tombstone(&mut self, tombstone: Tombstone) {
+        self.tombstones_modified = true;
+        self.giveaways.insert_tombstone(tombstone)
+    }
+
+    fn tombstone(&self, key: &str) -> Option<Tombstone> {
+        self.giveaways.tombstone(key)
+    }
+
+    fn is_buried(&self, key: &str) -> bool {
+        self.giveaways.is_buried(key)
+    }
+
+    fn iter_tombstones(&self) -> Box<dyn Iterator<Item = Tombstone> + '_> {
+        self.giveaways.iter_tombstones()
+    }
+
+    fn flush(&mut self) {
+        if self.modified {
+            save_json(self.giveaways.iter_all().collect::<Vec<SearchResult>>().iter(), &self.path);
+            self.modified = false;
+        }
+        if self.tombstones_modified {
+            save_json(self.giveaways.iter_tombstones().collect::<Vec<Tombstone>>().iter(), &tombstones_path(&self.path));
+            self.tombstones_modified = false;
+        }
+    }
+}
+
+/// Import a JSON database into the SQLite database of the configuration.
+pub fn migrate(json_file: &str, config: &Config) {
+    if config.database_backend != DatabaseBackend::Sqlite {
+        eprintln!("The database backend must be set to \"sqlite\" in the configuration file to migrate.");
+        std::process::exit(1);
+    }
+
+    let giveaways: Vec<SearchResult> = match read_json(json_file) {
+        Ok(giveaways) => giveaways,
+        Err(e) => {
+            eprintln!("Can't read {}: {}", json_file, e);
+            std::process::exit(1);
+        }
+    };
+    let len = giveaways.len();
+    let mut store = match SqliteStore::open(&config.database_file, None) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Can't open {}: {}", config.database_file, e);
+            std::process::exit(1);
+        }
+    };
+    for giveaway in giveaways {
+        store.upsert(giveaway);
+    }
+    let tombstones = read_tombstones(json_file);
+    let buried = tombstones.len();
+    for tombstone in tombstones {
+        store.insert_tombstone(tombstone);
+    }
+    store.flush();
+    println!("{} giveaways and {} tombstones imported into {}", len, buried, config.database_file);
+}
+
+/// Export the database to a JSON file.
+pub fn export(json_file: &str, config: &Config) {
+    let store = open_store(config);
+    let giveaways = store.iter_all().collect::<Vec<SearchResult>>();
+    save_json(giveaways.iter(), json_file);
+    let tombstones = store.iter_tombstones().collect::<Vec<Tombstone>>();
+    if !tombstones.is_empty() {
+        save_json(tombstones.iter(), &tombstones_path(json_file));
+    }
+    println!("{} giveaways and {} tombstones exported to {}", giveaways.len(), tombstones.len(), json_file);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::check_store;
+
+    #[test]
+    fn json_store() {
+        let path = std::env::temp_dir().join(format!("gleam_finder_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        check_store(&mut JsonStore::open(path, None).unwrap());
+        let store = JsonStore::open(path, None).unwrap();
+        assert_eq!(store.len(), 1);
+        assert!(store.is_buried("fghij"));
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(tombstones_path(path)).unwrap();
+    }
+
+    #[test]
+    fn recovery() {
+        let folder = std::env::temp_dir().join(format!("gleam_finder_recovery_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir(&folder).unwrap();
+        let path = folder.join("giveaways.json");
+        let path = path.to_str().unwrap();
+        let backups = BackupConfig {
+            interval: 1,
+            folder: folder.join("backups").to_str().unwrap().to_string(),
+            max: 5,
+        };
+
+        // Back up a database, and then truncate it and lose its tombstones
+        let mut store = JsonStore::open(path, Some(&backups)).unwrap();
+        store.upsert(crate::store::tests::giveaway("abcde", 10, 1000));
+        store.upsert(crate::store::tests::giveaway("fghij", 10, 1000));
+        store.upsert(crate::store::tests::giveaway("klmno", 10, 1000));
+        store.bury("klmno", "not found", 20);
+        store.flush();
+        crate::backup::backup(&store, &backups);
+        assert_eq!(backups_newest_first(&backups.folder).len(), 1);
+        let content = std::fs::read(path).unwrap();
+        std::fs::write(path, &content[..content.len() / 2]).unwrap();
+        std::fs::remove_file(tombstones_path(path)).unwrap();
+
+        let mut store = JsonStore::open(path, Some(&backups)).unwrap();
+        assert_eq!(store.len(), 2);
+        assert!(store.is_buried("klmno"));
+        assert!(std::path::Path::new(&format!("{}.corrupted", path)).exists());
+        store.flush();
+        assert_eq!(read_json::<SearchResult>(path).unwrap().len(), 2);
+        assert_eq!(read_tombstones(path).len(), 1);
+
+        // Without usable backups, the error is reported on every start
+        std::fs::write(path, &content[..content.len() / 2]).unwrap();
+        assert!(matches!(JsonStore::open(path, None), Err(Error::NoBackups)));
+        assert!(matches!(JsonStore::open(path, None), Err(Error::NoBackups)));
+        assert_eq!(std::fs::read(path).unwrap(), &content[..content.len() / 2]);
+        std::fs::remove_dir_all(&backups.folder).unwrap();
+        assert!(matches!(JsonStore::open(path, Some(&backups)), Err(Error::NoUsableBackup(_))));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn tombstones_file() {
+        assert_eq!(tombstones_path("giveaways.json"), "giveaways.tombstones.json");
+        assert_eq!(tombstones_path("data/giveaways"), "data/giveaways.tombstones.json");
+    }
+}
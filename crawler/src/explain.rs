@@ -0,0 +1,115 @@
+use crate::{config::Config, schema::load_campaigns};
+use format::{classifier::{classify, classify_explained, matching, EntryType}, entry::EntryMethod};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The number of entry types shown for each entry method which is not classified.
+const CANDIDATES: usize = 3;
+
+/// The entry methods of a campaign which are not recognized by the classifier.
+pub fn unclassified_entry_methods(giveaway: &Value) -> Vec<EntryMethod> {
+    entry_methods(giveaway).into_iter().filter(|entry_method| classify(entry_method).is_none()).collect()
+}
+
+/// The entry methods of a campaign which match several rules, with the entry types they match by priority.
+pub fn overlapping_entry_methods(giveaway: &Value) -> Vec<(EntryMethod, Vec<EntryType>)> {
+    entry_methods(giveaway).into_iter().filter_map(|entry_method| {
+        let entry_types = matching(&entry_method);
+        if entry_types.len() > 1 {
+            Some((entry_method, entry_types))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+fn entry_methods(giveaway: &Value) -> Vec<EntryMethod> {
+    let key = giveaway.pointer("/campaign/key").and_then(|key| key.as_str()).unwrap_or("unknown");
+    let mut entry_methods = Vec::new();
+    for entry_method in giveaway["entry_methods"].as_array().into_iter().flatten() {
+        match serde_json::from_value::<EntryMethod>(entry_method.clone()) {
+            Ok(entry_method) => entry_methods.push(entry_method),
+            Err(e) => eprintln!("Can't read an entry method of {}: {}", key, e),
+        }
+    }
+    entry_methods
+}
+
+/// Show why the entry methods of the campaigns are not classified, with the closest entry types.
+/// The campaigns are loaded like in the `schema` command, and can be filtered by key.
+pub async fn explain_classification(config: &Config, key: Option<&str>, fetch: bool) {
+    let mut total = 0;
+    let mut unclassified = 0;
+
+    for (giveaway, _) in load_campaigns(config, fetch).await {
+        let campaign_key = giveaway.pointer("/campaign/key").and_then(|key| key.as_str()).unwrap_or("unknown");
+        if key.map(|key| key != campaign_key).unwrap_or(false) {
+            continue;
+        }
+
+        total += giveaway["entry_methods"].as_array().map(|entry_methods| entry_methods.len()).unwrap_or(0);
+        for entry_method in unclassified_entry_methods(&giveaway) {
+            unclassified += 1;
+            println!("{} {} ({})", campaign_key, entry_method.id, entry_method.entry_type);
+            for explanation in classify_explained(&entry_method).iter().take(CANDIDATES) {
+                println!("    {}", explanation);
+            }
+        }
+    }
+
+    println!("{} of {} entry methods are not classified", unclassified, total);
+}
+
+/// Run every rule against the entry methods of the campaigns and show those matching several entry types.
+/// Their entry type only depends on the order of the rules.
+pub async fn explain_overlaps(config: &Config, key: Option<&str>, fetch: bool) {
+    let mut total = 0;
+    let mut overlaps: BTreeMap<String, usize> = BTreeMap::new();
+
+    for (giveaway, _) in load_campaigns(config, fetch).await {
+        let campaign_key = giveaway.pointer("/campaign/key").and_then(|key| key.as_str()).unwrap_or("unknown");
+        if key.map(|key| key != campaign_key).unwrap_or(false) {
+            continue;
+        }
+
+        total += giveaway["entry_methods"].as_array().map(|entry_methods| entry_methods.len()).unwrap_or(0);
+        for (entry_method, entry_types) in overlapping_entry_methods(&giveaway) {
+            let entry_types = entry_types.iter().map(|entry_type| entry_type.to_string()).collect::<Vec<String>>().join(", ");
+            println!("{} {} ({}) matches {}", campaign_key, entry_method.id, entry_method.entry_type, entry_types);
+            *overlaps.entry(entry_types).or_default() += 1;
+        }
+    }
+
+    for (entry_types, count) in &overlaps {
+        println!("{} entry methods match {}", count, entry_types);
+    }
+    println!("{} of {} entry methods match several rules", overlaps.values().sum::<usize>(), total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fetch::FixtureFetcher, gleam};
+    use format::classifier::EntryType;
+
+    #[test]
+    fn unclassified() {
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let (giveaway, _) = gleam::fetch_raw(&fetcher, "https://gleam.io/7qHd6/-").unwrap();
+
+        // The id of the account to follow is missing
+        let unclassified = unclassified_entry_methods(&giveaway);
+        assert_eq!(unclassified.len(), 1);
+        assert_eq!(unclassified[0].id, "3284619");
+        let explanations = classify_explained(&unclassified[0]);
+        assert_eq!(explanations[0].entry_type, EntryType::TwitterFollow);
+        assert_eq!(explanations[0].mismatches, vec![("2", "Expected Number, found Null")]);
+    }
+
+    #[test]
+    fn overlaps() {
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let (giveaway, _) = gleam::fetch_raw(&fetcher, "https://gleam.io/7qHd6/-").unwrap();
+        assert!(overlapping_entry_methods(&giveaway).is_empty());
+    }
+}
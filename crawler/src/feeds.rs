@@ -0,0 +1,172 @@
+use crate::{crawler::extract_gleam_links, fetch::{Fetcher, fetch_limited}, ratelimit::HostRateLimiter, sources::Source};
+use futures::future::{LocalBoxFuture, FutureExt};
+use std::sync::Arc;
+use string_tools::{get_all_after_strict, get_all_before_strict, get_all_between_strict};
+
+/// Discover pages by polling RSS and Atom feeds.
+/// Gleam.io links found in item bodies are returned directly, and item links are returned to be resolved.
+pub struct FeedSource {
+    fetcher: Arc<dyn Fetcher>,
+    limiter: Arc<HostRateLimiter>,
+    urls: Vec<String>,
+}
+
+impl FeedSource {
+    pub fn new(fetcher: Arc<dyn Fetcher>, limiter: Arc<HostRateLimiter>, urls: Vec<String>) -> FeedSource {
+        FeedSource { fetcher, limiter, urls }
+    }
+}
+
+impl Source for FeedSource {
+    fn name(&self) -> &'static str {
+        "feeds"
+    }
+
+    fn discover(&mut self) -> LocalBoxFuture<'_, Vec<String>> {
+        async move {
+            let mut results = Vec::new();
+            for url in &self.urls {
+                let feed_url = url.clone();
+                let feed = match fetch_limited(&self.fetcher, &self.limiter, url, move |fetcher| fetch_feed(fetcher, &feed_url)).await {
+                    Ok(feed) => feed,
+                    Err(e) => {
+                        eprintln!("Failed to load the feed {}: {}", url, e);
+                        continue;
+                    }
+                };
+
+                for url in parse_feed(&feed) {
+                    if !results.contains(&url) {
+                        results.push(url);
+                    }
+                }
+            }
+            results
+        }.boxed_local()
+    }
+}
+
+fn fetch_feed(fetcher: &dyn Fetcher, url: &str) -> Result<String, minreq::Error> {
+    let response = fetcher.get(url, &[
+        ("Accept", "application/rss+xml,application/atom+xml,application/xml,text/xml"),
+        ("User-Agent", "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:78.0) Gecko/20100101 Firefox/78.0"),
+    ])?;
+
+    Ok(response.as_str()?.to_string())
+}
+
+/// Get the attributes and the content of every `tag` element.
+/// Self-closing elements have an empty content.
+fn elements<'a>(mut xml: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    let opening = format!("<{}", tag);
+    let closing = format!("</{}>", tag);
+    let mut elements = Vec::new();
+
+    while let Some(rest) = get_all_after_strict(xml, &opening) {
+        xml = rest;
+
+        // Make sure we did not match a longer tag name
+        if !rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            continue;
+        }
+
+        let attributes = match get_all_before_strict(rest, ">") {
+            Some(attributes) => attributes,
+            None => break,
+        };
+        let rest = &rest[attributes.len() + 1..];
+
+        if attributes.ends_with('/') {
+            elements.push((attributes.trim_end_matches('/'), ""));
+            xml = rest;
+        } else if let Some(content) = get_all_before_strict(rest, &closing) {
+            elements.push((attributes, content));
+            xml = &rest[content.len() + closing.len()..];
+        } else {
+            break;
+        }
+    }
+
+    elements
+}
+
+/// Remove CDATA markers and decode the entities used to escape HTML in feeds.
+fn text(content: &str) -> String {
+    let content = content.trim();
+    let content = get_all_between_strict(content, "<![CDATA[", "]]>").unwrap_or(content);
+    content
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Extract candidate urls from an RSS or Atom feed.
+pub fn parse_feed(xml: &str) -> Vec<String> {
+    let mut gleam_links = Vec::new();
+    let mut links = Vec::new();
+
+    let mut items = elements(xml, "item");
+    items.append(&mut elements(xml, "entry"));
+
+    for (_attributes, item) in items {
+        // Links to the articles (RSS uses the content, Atom uses the href attribute)
+        for (attributes, content) in elements(item, "link") {
+            let link = match get_all_between_strict(attributes, "href=\"", "\"") {
+                Some(href) => text(href),
+                None => text(content),
+            };
+            if link.starts_with("http") && !links.contains(&link) {
+                links.push(link);
+            }
+        }
+
+        // Gleam.io links directly included in the article
+        for tag in &["description", "content:encoded", "content", "summary"] {
+            for (_attributes, content) in elements(item, tag) {
+                for link in extract_gleam_links(&text(content)) {
+                    if !gleam_links.contains(&link) {
+                        gleam_links.push(link);
+                    }
+                }
+            }
+        }
+    }
+
+    for link in links {
+        if !gleam_links.contains(&link) {
+            gleam_links.push(link);
+        }
+    }
+    gleam_links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rss_feed() {
+        let urls = parse_feed(include_str!("../test_data/feeds/rss.xml"));
+        assert_eq!(urls, vec![
+            "https://gleam.io/7qHd6/-",
+            "https://gleam.io/2zAsX/-",
+            "https://giveaways.example.com/2020/10/win-a-gaming-pc",
+            "https://giveaways.example.com/2020/10/headphones-giveaway",
+            "https://giveaways.example.com/2020/10/nothing-here",
+        ]);
+    }
+
+    #[test]
+    fn atom_feed() {
+        let urls = parse_feed(include_str!("../test_data/feeds/atom.xml"));
+        assert_eq!(urls, vec![
+            "https://gleam.io/OWMw8/-",
+            "https://gleam.io/lSq1Q/-",
+            "https://blog.example.org/posts/steam-keys",
+            "https://blog.example.org/posts/console-bundle",
+        ]);
+    }
+}
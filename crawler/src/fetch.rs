@@ -0,0 +1,159 @@
+use crate::ratelimit::HostRateLimiter;
+use std::{collections::HashMap, sync::Arc};
+use chrono::DateTime;
+use tokio::task;
+use url::Url;
+
+/// A response to an HTTP GET request.
+pub struct Response {
+    pub status_code: i32,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _value)| key.eq_ignore_ascii_case(name)).map(|(_key, value)| value.as_str())
+    }
+
+    /// Get the number of seconds to wait before retrying, according to the `Retry-After` header.
+    /// It can be a number of seconds or a date.
+    pub fn retry_after(&self, now: u64) -> Option<u64> {
+        let value = self.header("Retry-After")?.trim();
+        if let Ok(seconds) = value.parse() {
+            return Some(seconds);
+        }
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        Some((date.timestamp() as u64).saturating_sub(now))
+    }
+
+    pub fn as_str(&self) -> Result<&str, minreq::Error> {
+        std::str::from_utf8(&self.body).map_err(minreq::Error::InvalidUtf8InBody)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+/// Everything the crawler downloads goes through a fetcher.
+/// This allows replacing the network with saved pages in tests.
+pub trait Fetcher: Send + Sync {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, minreq::Error>;
+}
+
+/// A fetcher sending real requests.
+pub struct MinreqFetcher;
+
+impl Fetcher for MinreqFetcher {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, minreq::Error> {
+        let mut request = minreq::get(url);
+        for (name, value) in headers {
+            request = request.with_header(*name, *value);
+        }
+        let response = request.send()?;
+
+        Ok(Response {
+            status_code: response.status_code,
+            headers: response.headers.clone(),
+            body: response.into_bytes(),
+        })
+    }
+}
+
+pub fn url_to_host(url: &str) -> String {
+    if let Ok(url) = &Url::parse(url) {
+        url.host_str().unwrap_or("unknown").to_string()
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// Wait until the limiter allows a request to the host of `url`, then run `request` on a blocking thread.
+/// The runtime keeps running other tasks in the meantime, including the ones waiting for other hosts.
+pub async fn fetch_limited<T, F>(fetcher: &Arc<dyn Fetcher>, limiter: &HostRateLimiter, url: &str, request: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn Fetcher) -> T + Send + 'static,
+{
+    limiter.acquire(&url_to_host(url)).await;
+    let fetcher = Arc::clone(fetcher);
+    task::spawn_blocking(move || request(fetcher.as_ref())).await.expect("Failed to join the loading task")
+}
+
+/// A saved response.
+/// It can be the name of a file to serve, or a complete response.
+#[cfg(test)]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Fixture {
+    File(String),
+    Response {
+        status: i32,
+        file: Option<String>,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+/// A fetcher replaying saved pages.
+/// The folder must contain an `index.json` file mapping urls to fixtures.
+#[cfg(test)]
+pub struct FixtureFetcher {
+    folder: std::path::PathBuf,
+    index: HashMap<String, Fixture>,
+}
+
+#[cfg(test)]
+impl FixtureFetcher {
+    pub fn new(folder: &str) -> FixtureFetcher {
+        let folder = std::path::PathBuf::from(folder);
+        let index = std::fs::read_to_string(folder.join("index.json")).expect("Can't read fixture index");
+        FixtureFetcher {
+            index: serde_json::from_str(&index).expect("Invalid fixture index"),
+            folder,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Fetcher for FixtureFetcher {
+    fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response, minreq::Error> {
+        let (status_code, file, headers) = match self.index.get(url) {
+            Some(Fixture::File(file)) => (200, Some(file), HashMap::new()),
+            Some(Fixture::Response { status, file, headers }) => (*status, file.as_ref(), headers.clone()),
+            None => return Err(minreq::Error::Other("no fixture for this url")),
+        };
+
+        Ok(Response {
+            status_code,
+            headers,
+            body: match file {
+                Some(file) => std::fs::read(self.folder.join(file)).map_err(minreq::Error::IoError)?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after() {
+        let mut response = Response {
+            status_code: 429,
+            headers: HashMap::new(),
+            body: Vec::new(),
+        };
+        assert_eq!(response.retry_after(0), None);
+
+        response.headers.insert(String::from("retry-after"), String::from("120"));
+        assert_eq!(response.retry_after(0), Some(120));
+
+        response.headers.insert(String::from("retry-after"), String::from("Wed, 14 Oct 2020 12:02:00 GMT"));
+        assert_eq!(response.retry_after(1602676800), Some(120));
+        assert_eq!(response.retry_after(1602680000), Some(0));
+    }
+}
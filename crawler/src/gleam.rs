@@ -1,6 +1,7 @@
 use std::time::{SystemTime, UNIX_EPOCH};
-use format::parsing::*;
+use format::{parsing::*, strictness::Strictness};
 use format::prelude::*;
+use crate::{fetch::{Fetcher, Response}, quarantine::quarantine};
 
 /// Extract the id of the giveaway from an url.
 pub fn get_gleam_id(url: &str) -> Option<&str> {
@@ -15,87 +16,217 @@ pub fn get_gleam_id(url: &str) -> Option<&str> {
 #[derive(Debug)]
 pub enum Error {
     InvalidGleamUrl,
+    /// The request did not complete in time
+    Timeout,
+    /// The request failed before getting a response (DNS failure, connection reset...)
     NetworkError(minreq::Error),
+    /// The campaign does not exist (404 or 410)
+    NotFound(i32),
+    /// Gleam.io asked us to slow down (429), and maybe told us when to retry (in seconds)
+    RateLimited { retry_after: Option<u64> },
+    /// A 5xx status
+    ServerFailure(i32),
+    /// Any other unexpected status
+    UnexpectedStatus(i32),
     ParseError(ParseError),
 }
 
-pub fn fetch(url: &str) -> Result<SearchResult, Error> {
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidGleamUrl => write!(f, "invalid gleam.io url"),
+            Error::Timeout => write!(f, "timeout"),
+            Error::NetworkError(e) => write!(f, "network error: {}", e),
+            Error::NotFound(status) => write!(f, "campaign not found (status {})", status),
+            Error::RateLimited { retry_after: Some(retry_after) } => write!(f, "rate limited (retry after {}s)", retry_after),
+            Error::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Error::ServerFailure(status) => write!(f, "server error (status {})", status),
+            Error::UnexpectedStatus(status) => write!(f, "unexpected status {}", status),
+            Error::ParseError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<minreq::Error> for Error {
+    fn from(error: minreq::Error) -> Error {
+        match error {
+            minreq::Error::IoError(e) if e.kind() == std::io::ErrorKind::TimedOut || e.kind() == std::io::ErrorKind::WouldBlock => Error::Timeout,
+            e => Error::NetworkError(e),
+        }
+    }
+}
+
+/// How to parse the pages, and what to do with the pages which can't be parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub strictness: Strictness,
+    /// The folder in which the pages which can't be parsed are saved
+    pub quarantine_folder: Option<String>,
+}
+
+/// Load the page of a campaign and get its normalized url.
+fn load_page(fetcher: &dyn Fetcher, url: &str) -> Result<(String, Response), Error> {
     let giveaway_id = match get_gleam_id(url) {
         Some(id) => id,
         None => return Err(Error::InvalidGleamUrl),
     };
 
     let url = format!("https://gleam.io/{}/-", giveaway_id);
-    let response = match minreq::get(&url)
-        .with_header("Host", "gleam.io")
-        .with_header(
-            "User-Agent",
-            "Mozilla/5.0 (X11; Linux x86_64; rv:72.0) Gecko/20100101 Firefox/72.0",
-        )
-        .with_header("Accept", "text/html")
-        .with_header("DNT", "1")
-        .with_header("Connection", "keep-alive")
-        .with_header("Upgrade-Insecure-Requests", "1")
-        .with_header("TE", "Trailers")
-        .send()
-    {
+    let response = match fetcher.get(&url, &[
+        ("Host", "gleam.io"),
+        ("User-Agent", "Mozilla/5.0 (X11; Linux x86_64; rv:72.0) Gecko/20100101 Firefox/72.0"),
+        ("Accept", "text/html"),
+        ("DNT", "1"),
+        ("Connection", "keep-alive"),
+        ("Upgrade-Insecure-Requests", "1"),
+        ("TE", "Trailers"),
+    ]) {
         Ok(response) => response,
         Err(e) => {
-            return Err(Error::NetworkError(e));
+            return Err(e.into());
         },
     };
 
-    let body = match response.as_str() {
-        Ok(body) => body,
-        Err(e) => return Err(Error::NetworkError(e)),
-    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    match response.status_code {
+        200..=299 => Ok((url, response)),
+        404 | 410 => Err(Error::NotFound(response.status_code)),
+        429 => Err(Error::RateLimited { retry_after: response.retry_after(now) }),
+        500..=599 => Err(Error::ServerFailure(response.status_code)),
+        status => Err(Error::UnexpectedStatus(status)),
+    }
+}
+
+/// Load the campaign and contestant data of a page as gleam.io sends them.
+pub fn fetch_raw(fetcher: &dyn Fetcher, url: &str) -> Result<(serde_json::Value, serde_json::Value), Error> {
+    let (url, response) = load_page(fetcher, url)?;
+    format::parsing::parse_raw_html(response.as_str()?).map_err(|mut e| {
+        e.set_url(&url);
+        Error::ParseError(e)
+    })
+}
+
+/// Load a campaign, and also get everything gleam.io tells about it as a JSON value.
+/// The `SearchResult` only keeps the fields we use, while the JSON value is the campaign exactly as gleam.io sent it.
+/// Pages which can't be parsed are quarantined if a folder is set in the options.
+pub fn fetch(fetcher: &dyn Fetcher, url: &str, options: &ParseOptions) -> Result<(SearchResult, serde_json::Value), Error> {
+    let (url, response) = load_page(fetcher, url)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let body = response.as_str()?;
 
-    let (giveaway, entry_count) = match format::parsing::parse_html(body) {
+    let (giveaway, entry_count) = match format::parsing::parse_html(body, options.strictness) {
         Ok((giveaway, _, entry_count)) => (giveaway, entry_count),
-        Err(e) => return Err(Error::ParseError(e)),
+        Err(mut e) => {
+            e.set_url(&url);
+            if let Some(folder) = &options.quarantine_folder {
+                let key = get_gleam_id(&url).expect("The url has been normalized");
+                if let Err(io_error) = quarantine(folder, key, &url, body, &e, now) {
+                    eprintln!("Failed to quarantine {}: {}", url, io_error);
+                }
+            }
+            return Err(Error::ParseError(e));
+        },
     };
     
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    // Serializing the giveaway again would fill in the missing optional fields
+    let (snapshot, _) = parse_raw_html(body).map_err(|mut e| {
+        e.set_url(&url);
+        Error::ParseError(e)
+    })?;
     let entry_evolution = match entry_count {
         Some(e) => {
             let mut hashmap = std::collections::HashMap::new();
-            hashmap.insert(now, e);
+            hashmap.insert(now.to_string(), e);
             Some(hashmap)
         },
         None => None
     };
     
-    Ok(SearchResult {
+    Ok((SearchResult {
         giveaway: giveaway.into(),
         last_updated: now,
+        failed_at: None,
+        failures: 0,
         referers: vec![url],
         entry_count,
         entry_evolution,
-    })
+    }, snapshot))
 }
+
 #[cfg(test)]
 mod tests {
-    use std::{thread::sleep, time::Duration};
     use super::*;
+    use crate::fetch::FixtureFetcher;
 
     #[test]
     fn test_giveaway_struct() {
-        let giveaway =
-            fetch("https://gleam.io/29CPn/-2-alok-gveaway-and-12000-diamonds-")
-                .unwrap();
-        println!("{:?}", giveaway);
-            sleep(Duration::from_secs(15));
-        let giveaway =
-            fetch("https://gleam.io/SB3C7/-")
-               .unwrap();
-        println!("{:?}", giveaway);
-        sleep(Duration::from_secs(15));
-        let giveaway = fetch("https://gleam.io/8nTqy/amd-5700xt-gpu").unwrap();
-        println!("{:?}", giveaway);
-        sleep(Duration::from_secs(15));
-        let giveaway =
-            fetch("https://gleam.io/ff3QT/win-an-ipad-pro-with-canstar").unwrap();
-        println!("{:?}", giveaway);
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let (giveaway, snapshot) = fetch(&fetcher, "https://gleam.io/7qHd6/sorteo", &ParseOptions::default()).unwrap();
+        assert_eq!(giveaway.giveaway.campaign.key, "7qHd6");
+        assert_eq!(giveaway.get_name(), "Sorteo de una tarjeta gráfica");
+        assert_eq!(giveaway.giveaway.incentive.name, "AMD 5700XT GPU");
+        assert_eq!(giveaway.giveaway.entry_methods.len(), 1);
+        assert_eq!(giveaway.entry_count, Some(1337));
+        assert_eq!(giveaway.referers, vec!["https://gleam.io/7qHd6/-"]);
+        assert_eq!(snapshot["campaign"]["key"], "7qHd6");
+        assert_eq!(snapshot["appName"], "gleam");
+
+        assert!(matches!(fetch(&fetcher, "https://gleam.io/zzzzz/-", &ParseOptions::default()), Err(Error::ParseError(ParseError::GiveawayJsonNotFound))));
+        assert!(matches!(fetch(&fetcher, "https://gleam.io/aaaaa/-", &ParseOptions::default()), Err(Error::NetworkError(_))));
+        assert!(matches!(fetch(&fetcher, "https://gleam.io/gone1/-", &ParseOptions::default()), Err(Error::NotFound(410))));
+        assert!(matches!(fetch(&fetcher, "https://gleam.io/busy1/-", &ParseOptions::default()), Err(Error::RateLimited { retry_after: Some(120) })));
+        assert!(matches!(fetch(&fetcher, "https://gleam.io/errr1/-", &ParseOptions::default()), Err(Error::ServerFailure(503))));
+        assert!(matches!(fetch(&fetcher, "https://example.com", &ParseOptions::default()), Err(Error::InvalidGleamUrl)));
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let strict = ParseOptions { strictness: Strictness::Strict, ..ParseOptions::default() };
+        assert!(fetch(&fetcher, "https://gleam.io/7qHd6/-", &strict).is_ok());
+
+        // New fields are kept in lenient mode
+        let (giveaway, snapshot) = fetch(&fetcher, "https://gleam.io/drft1/-", &ParseOptions::default()).unwrap();
+        assert_eq!(giveaway.giveaway.campaign.key, "drft1");
+        assert_eq!(snapshot["campaign"]["require_captcha"], true);
+        assert_eq!(snapshot["entry_methods"][0]["streak"], 3);
+
+        // And reported in strict mode
+        match fetch(&fetcher, "https://gleam.io/drft1/-", &strict) {
+            Err(e @ Error::ParseError(ParseError::UnknownFields(_))) => assert_eq!(e.to_string(), "unknown fields (Campaign: require_captcha; EntryMethod: streak)"),
+            _ => panic!("unknown fields were not reported"),
+        }
+    }
+
+    #[test]
+    fn quarantined_pages() {
+        let folder = std::env::temp_dir().join(format!("gleam_finder_quarantine_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        let options = ParseOptions {
+            quarantine_folder: Some(folder.to_str().unwrap().to_string()),
+            ..ParseOptions::default()
+        };
+
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let error = match fetch(&fetcher, "https://gleam.io/brkn1/whatever", &options) {
+            Err(Error::ParseError(ParseError::GiveawayFormatError(e))) => e,
+            _ => panic!("the page should not be valid"),
+        };
+        assert_eq!(error.path, "entry_methods[0].worth");
+        assert_eq!(error.key.as_deref(), Some("brkn1"));
+        assert_eq!(error.url.as_deref(), Some("https://gleam.io/brkn1/-"));
+        assert!(error.snippet.contains("\"worth\": \"one\""));
+
+        // Valid pages are not quarantined
+        fetch(&fetcher, "https://gleam.io/7qHd6/-", &options).unwrap();
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 3);
+
+        // The quarantine can be replayed
+        let quarantined = FixtureFetcher::new(folder.to_str().unwrap());
+        let replayed = fetch(&quarantined, "https://gleam.io/brkn1/-", &ParseOptions::default()).unwrap_err();
+        assert_eq!(replayed.to_string(), Error::ParseError(ParseError::GiveawayFormatError(error)).to_string());
+
+        std::fs::remove_dir_all(&folder).unwrap();
     }
 
     #[test]
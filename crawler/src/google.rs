@@ -1,14 +1,62 @@
 use string_tools::{get_all_after, get_all_between_strict};
+use std::sync::Arc;
+use futures::future::{LocalBoxFuture, FutureExt};
+use progress_bar::{color::*, progress_bar::ProgressBar};
+use crate::{fetch::{Fetcher, fetch_limited}, ratelimit::HostRateLimiter, sources::Source};
 
-pub fn search(page: usize) -> Result<Vec<String>, minreq::Error> {
-    let response = match minreq::get(get_full_url(page))
-        .with_header("Accept", "text/plain")
-        .with_header("Host", "www.google.com")
-        .with_header(
-            "User-Agent",
-            "Mozilla/5.0 (X11; Linux x86_64; rv:71.0) Gecko/20100101 Firefox/71.0",
-        )
-        .send() {
+/// Discover pages by browsing the Google results of the last hour.
+pub struct GoogleSource {
+    fetcher: Arc<dyn Fetcher>,
+    limiter: Arc<HostRateLimiter>,
+}
+
+impl GoogleSource {
+    pub fn new(fetcher: Arc<dyn Fetcher>, limiter: Arc<HostRateLimiter>) -> GoogleSource {
+        GoogleSource { fetcher, limiter }
+    }
+}
+
+impl Source for GoogleSource {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    fn discover(&mut self) -> LocalBoxFuture<'_, Vec<String>> {
+        async move {
+            let mut progress_bar = ProgressBar::new(7);
+            progress_bar.set_action("Searching", Color::White, Style::Normal);
+            let mut results = Vec::new();
+            let mut page = 0;
+            loop {
+                progress_bar.set_action("Loading", Color::Blue, Style::Normal);
+                progress_bar.print_info("Getting", &format!("the results page {}", page), Color::Blue, Style::Normal);
+                let new_results = fetch_limited(&self.fetcher, &self.limiter, &get_full_url(page), move |fetcher| search(fetcher, page)).await.unwrap_or_default();
+                if !new_results.is_empty() {
+                    for new_result in new_results {
+                        results.push(new_result);
+                    }
+                    page += 1;
+                    progress_bar.inc();
+                } else {
+                    break;
+                }
+            }
+            progress_bar.set_action("Finished", Color::Green, Style::Bold);
+            progress_bar.print_info("Finished", &format!("{} results found", results.len()), Color::Green, Style::Bold);
+            progress_bar.finalize();
+            println!();
+
+            results
+        }.boxed_local()
+    }
+}
+
+pub fn search(fetcher: &dyn Fetcher, page: usize) -> Result<Vec<String>, minreq::Error> {
+    let response = match fetcher.get(&get_full_url(page), &[
+        ("Accept", "text/plain"),
+        ("Host", "www.google.com"),
+        ("User-Agent", "Mozilla/5.0 (X11; Linux x86_64; rv:71.0) Gecko/20100101 Firefox/71.0"),
+    ]) {
             Ok(response) => response,
             Err(e) => {
                 eprintln!("Failed to load google search page: {}", e);
@@ -74,6 +122,7 @@ fn get_full_url(page: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fetch::FixtureFetcher;
 
     #[test]
     fn get_full_url_test() {
@@ -85,10 +134,11 @@ mod tests {
 
     #[test]
     fn resolve_google_request() {
-        let result = search(0).unwrap();
-        assert!(!result.is_empty());
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let result = search(&fetcher, 0).unwrap();
+        assert_eq!(result, vec!["https://example.com/win-a-gpu", "https://example.org/nothing"]);
 
-        let result = search(9).unwrap();
+        let result = search(&fetcher, 1).unwrap();
         assert!(result.is_empty());
     }
 }
\ No newline at end of file
@@ -7,12 +7,32 @@ mod gleam;
 mod meilisearch;
 mod database;
 mod backup;
+mod sources;
+mod feeds;
+mod sitemap;
+mod sqlite;
+mod store;
+mod ratelimit;
+mod fetch;
+mod scheduler;
+mod cron;
+mod daemon;
+mod retry;
+mod archive;
+mod schema;
+mod quarantine;
+mod explain;
 use config::*;
 use stats::*;
 use crawler::launch;
 use meilisearch::init_meilisearch;
 use config::configurate;
 use backup::backup;
+use database::{migrate, export};
+use store::open_store;
+use archive::print_diff;
+use schema::check_schema;
+use explain::{explain_classification, explain_overlaps};
 
 #[tokio::main]
 async fn main() {
@@ -33,9 +53,35 @@ async fn main() {
         (@subcommand backup =>
             (about: "Backup the database")
         )
+        (@subcommand migrate =>
+            (about: "Import a JSON database into the SQLite database")
+            (@arg FILE: +required "The JSON file to import")
+        )
+        (@subcommand export =>
+            (about: "Export the database to a JSON file")
+            (@arg FILE: +required "The JSON file to create")
+        )
+        (@subcommand diff =>
+            (about: "Show how a campaign changed between two archived snapshots, or over its whole history")
+            (@arg KEY: +required "The key of the campaign")
+            (@arg FROM: "The timestamp of the first snapshot")
+            (@arg TO: "The timestamp of the second snapshot (the last one by default)")
+        )
+        (@subcommand schema =>
+            (about: "Compare the fields sent by gleam.io with the saved baseline, using the archived snapshots")
+            (@arg fetch: -f --fetch "Load the pages of the running giveaways instead of using the archive")
+            (@arg save: -s --save "Save the observed fields as the new baseline")
+        )
+        (@subcommand explain =>
+            (about: "Show why entry methods are not classified, with the closest entry types and the fields which do not match")
+            (@arg KEY: "Only explain the entry methods of this campaign")
+            (@arg fetch: -f --fetch "Load the pages of the running giveaways instead of using the archive")
+            (@arg overlaps: -o --overlaps "Show the entry methods matching several entry types instead, whose type depends on the order of the rules")
+        )
         (@subcommand launch =>
-            (about: "Launch the bot")
+            (about: "Launch the bot as a daemon, running its jobs on the schedules of the configuration file")
             (@arg fast: -f --f "Do not load gleam.io pages and do not save them")
+            (@arg quarantine: --quarantine +takes_value "Save the pages which can't be parsed to this folder")
         )
     ).get_matches();
 
@@ -44,13 +90,32 @@ async fn main() {
     };
 
     match matches.subcommand() {
-        ("stats", Some(_args)) => stats(config()),
-        ("init_meilisearch", Some(_args)) => init_meilisearch(&config()).await,
+        ("stats", Some(_args)) => stats(open_store(&config()).as_ref()),
+        ("init_meilisearch", Some(_args)) => {
+            let config = config();
+            init_meilisearch(open_store(&config).as_ref(), &config).await
+        },
         ("configurate", Some(_args)) => configurate(),
-        ("backup", Some(_args)) => backup(&config().database_file, &config().backups.expect("Please configurate backups")),
+        ("backup", Some(_args)) => {
+            let config = config();
+            backup(open_store(&config).as_ref(), &config.backups.expect("Please configurate backups"))
+        },
+        ("migrate", Some(args)) => migrate(args.value_of("FILE").unwrap(), &config()),
+        ("export", Some(args)) => export(args.value_of("FILE").unwrap(), &config()),
+        ("diff", Some(args)) => {
+            let timestamp = |name| args.value_of(name).map(|t: &str| t.parse::<u64>().expect("Timestamps must be numbers"));
+            print_diff(args.value_of("KEY").unwrap(), timestamp("FROM"), timestamp("TO"), &config())
+        },
+        ("schema", Some(args)) => check_schema(&config(), args.is_present("fetch"), args.is_present("save")).await,
+        ("explain", Some(args)) if args.is_present("overlaps") => explain_overlaps(&config(), args.value_of("KEY"), args.is_present("fetch")).await,
+        ("explain", Some(args)) => explain_classification(&config(), args.value_of("KEY"), args.is_present("fetch")).await,
         ("launch", Some(args)) => {
             let fast: bool = args.value_of("fast").unwrap_or("false").parse().unwrap();
-            launch(config(), fast).await;
+            let mut config = config();
+            if let Some(folder) = args.value_of("quarantine") {
+                config.quarantine_folder = Some(folder.to_string());
+            }
+            launch(config, fast).await;
         },
         (name, Some(_args)) => {
             println!("Unknown subcommand: {:?}", name);
@@ -0,0 +1,49 @@
+use format::parsing::ParseError;
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, io, path::Path, sync::Mutex};
+
+/// Pages are quarantined from several threads, but the index is a single file.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Save a page which could not be parsed, so that it can be replayed as a test case.
+///
+/// The folder can be used as fixtures: `index.json` maps the url of each page to its last copy,
+/// and the error is written next to the page.
+pub fn quarantine(folder: &str, key: &str, url: &str, body: &str, error: &ParseError, now: u64) -> io::Result<()> {
+    let folder = Path::new(folder);
+    fs::create_dir_all(folder)?;
+
+    let filename = format!("{}-{}.html", key, now);
+    fs::write(folder.join(&filename), body)?;
+    fs::write(folder.join(format!("{}-{}.error.txt", key, now)), format!("{}\n\n{}\n", url, error))?;
+
+    let _lock = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let index_path = folder.join("index.json");
+    let mut index: BTreeMap<String, Value> = match fs::read_to_string(&index_path) {
+        Ok(index) => serde_json::from_str(&index)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e),
+    };
+    index.insert(url.to_string(), Value::String(filename));
+    fs::write(index_path, serde_json::to_string_pretty(&index)?)
+}
+
+/// Get the last quarantined copy of each page, with the key of its campaign and the time it was quarantined.
+pub fn quarantined_pages(folder: &str) -> io::Result<Vec<(String, u64, String)>> {
+    let folder = Path::new(folder);
+    let index: BTreeMap<String, String> = match fs::read_to_string(folder.join("index.json")) {
+        Ok(index) => serde_json::from_str(&index)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut pages = Vec::new();
+    for filename in index.values() {
+        let (key, quarantined_at) = match filename.strip_suffix(".html").and_then(|name| name.split_once('-')) {
+            Some((key, quarantined_at)) => (key, quarantined_at.parse().unwrap_or(0)),
+            None => continue,
+        };
+        pages.push((key.to_string(), quarantined_at, fs::read_to_string(folder.join(filename))?));
+    }
+    Ok(pages)
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+/// A token bucket refilled with one token every `interval`.
+/// Tokens can be borrowed: the bucket then tells how long the borrower has to wait.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// No request is allowed before this time, whatever the number of tokens
+    paused_until: Instant,
+}
+
+impl Bucket {
+    fn new(burst: usize, now: Instant) -> Bucket {
+        Bucket {
+            tokens: burst as f64,
+            updated: now,
+            paused_until: now,
+        }
+    }
+
+    /// Take a token and get the time to wait before using it.
+    fn reserve(&mut self, now: Instant, interval: Duration, burst: usize) -> Duration {
+        let pause = self.paused_until.saturating_duration_since(now);
+        if interval == Duration::from_secs(0) {
+            return pause;
+        }
+
+        if now > self.updated {
+            let refilled = (now - self.updated).as_secs_f64() / interval.as_secs_f64();
+            self.tokens = (self.tokens + refilled).min(burst as f64);
+            self.updated = now;
+        }
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            pause
+        } else {
+            interval.mul_f64(-self.tokens).max(pause)
+        }
+    }
+}
+
+/// A rate limiter shared by all the tasks of the crawler.
+/// Each host has its own bucket, so that requests to different hosts never wait for each other.
+pub struct HostRateLimiter {
+    interval: Duration,
+    burst: usize,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl HostRateLimiter {
+    /// Allow one request to a host every `interval`, with bursts of `burst` requests.
+    pub fn new(interval: Duration, burst: usize) -> HostRateLimiter {
+        HostRateLimiter {
+            interval,
+            burst: burst.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn reserve(&self, host: &str) -> Duration {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let burst = self.burst;
+        buckets
+            .entry(host.to_string())
+            .or_insert_with(|| Bucket::new(burst, now))
+            .reserve(now, self.interval, burst)
+    }
+
+    /// Wait until a request to `host` is allowed.
+    pub async fn acquire(&self, host: &str) {
+        let delay = self.reserve(host);
+        if delay > Duration::from_secs(0) {
+            tokio::time::delay_for(delay).await;
+        }
+    }
+
+    /// Prevent any request to `host` for `duration`, after an error for example.
+    pub fn pause(&self, host: &str, duration: Duration) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(host.to_string()).or_insert_with(|| Bucket::new(self.burst, now));
+        bucket.paused_until = bucket.paused_until.max(now + duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(10);
+        let mut bucket = Bucket::new(2, start);
+
+        // The burst is available immediately
+        assert_eq!(bucket.reserve(start, interval, 2), Duration::from_secs(0));
+        assert_eq!(bucket.reserve(start, interval, 2), Duration::from_secs(0));
+
+        // Then requests are spaced by the interval
+        assert_eq!(bucket.reserve(start, interval, 2), Duration::from_secs(10));
+        assert_eq!(bucket.reserve(start, interval, 2), Duration::from_secs(20));
+        assert_eq!(bucket.reserve(start + Duration::from_secs(25), interval, 2), Duration::from_secs(5));
+
+        // Tokens do not accumulate beyond the burst
+        let later = start + Duration::from_secs(1000);
+        assert_eq!(bucket.reserve(later, interval, 2), Duration::from_secs(0));
+        assert_eq!(bucket.reserve(later, interval, 2), Duration::from_secs(0));
+        assert_eq!(bucket.reserve(later, interval, 2), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn hosts_are_independent() {
+        let limiter = HostRateLimiter::new(Duration::from_secs(60), 1);
+        assert_eq!(limiter.reserve("gleam.io"), Duration::from_secs(0));
+        assert_eq!(limiter.reserve("example.com"), Duration::from_secs(0));
+        assert!(limiter.reserve("gleam.io") > Duration::from_secs(50));
+
+        limiter.pause("example.com", Duration::from_secs(120));
+        let delay = limiter.reserve("example.com");
+        assert!(delay > Duration::from_secs(110) && delay <= Duration::from_secs(120));
+
+        // Pauses work without cooldown too
+        let limiter = HostRateLimiter::new(Duration::from_secs(0), 1);
+        assert_eq!(limiter.reserve("gleam.io"), Duration::from_secs(0));
+        limiter.pause("gleam.io", Duration::from_secs(30));
+        assert!(limiter.reserve("gleam.io") > Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::gleam::Error;
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+
+mod defaults {
+    pub(super) const fn max_attempts() -> usize {3}
+    pub(super) const fn base_delay() -> u64 {10}
+    pub(super) const fn max_delay() -> u64 {600}
+}
+
+/// What to do with a campaign which could not be loaded.
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    /// Try again after this delay
+    Retry(Duration),
+    /// Keep the data we have, but do not try again before the next update
+    MarkStale,
+    /// The campaign does not exist anymore
+    Remove,
+}
+
+/// Decides how to react to errors when loading a campaign.
+/// Temporary failures are retried with an exponential backoff.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// The maximum number of requests for a single campaign, including the first one
+    #[serde(default = "defaults::max_attempts")]
+    pub max_attempts: usize,
+    /// The delay before the first retry (in seconds), doubled after each failure
+    #[serde(default = "defaults::base_delay")]
+    pub base_delay: u64,
+    /// The maximum delay before a retry (in seconds)
+    #[serde(default = "defaults::max_delay")]
+    pub max_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: defaults::max_attempts(),
+            base_delay: defaults::base_delay(),
+            max_delay: defaults::max_delay(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: usize) -> u64 {
+        let factor = 1u64.checked_shl(attempt.saturating_sub(1) as u32).unwrap_or(u64::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Decide what to do after the `attempt`-th request for a campaign failed (starting at 1).
+    pub fn decide(&self, error: &Error, attempt: usize) -> Decision {
+        let delay = match error {
+            Error::InvalidGleamUrl | Error::NotFound(_) => return Decision::Remove,
+
+            // The page was served but it is not a campaign we understand, which is often temporary (captcha, maintenance page...)
+            Error::ParseError(_) | Error::UnexpectedStatus(_) => return Decision::MarkStale,
+
+            // Do not retry sooner than asked, but do not block the crawler for too long either
+            Error::RateLimited { retry_after: Some(retry_after) } if *retry_after > self.max_delay => return Decision::MarkStale,
+            Error::RateLimited { retry_after: Some(retry_after) } => (*retry_after).max(self.backoff(attempt)),
+
+            Error::RateLimited { retry_after: None } | Error::ServerFailure(_) | Error::Timeout | Error::NetworkError(_) => self.backoff(attempt),
+        };
+
+        if attempt >= self.max_attempts {
+            Decision::MarkStale
+        } else {
+            Decision::Retry(Duration::from_secs(delay))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use format::parsing::ParseError;
+
+    #[test]
+    fn decisions() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.decide(&Error::NotFound(404), 1), Decision::Remove);
+        assert_eq!(policy.decide(&Error::NotFound(410), 1), Decision::Remove);
+        assert_eq!(policy.decide(&Error::ParseError(ParseError::GiveawayJsonNotFound), 1), Decision::MarkStale);
+        assert_eq!(policy.decide(&Error::UnexpectedStatus(403), 1), Decision::MarkStale);
+
+        // Exponential backoff
+        assert_eq!(policy.decide(&Error::Timeout, 1), Decision::Retry(Duration::from_secs(10)));
+        assert_eq!(policy.decide(&Error::ServerFailure(503), 2), Decision::Retry(Duration::from_secs(20)));
+        assert_eq!(policy.decide(&Error::ServerFailure(503), 3), Decision::MarkStale);
+        let patient = RetryPolicy { max_attempts: 100, ..RetryPolicy::default() };
+        assert_eq!(patient.decide(&Error::Timeout, 6), Decision::Retry(Duration::from_secs(320)));
+        assert_eq!(patient.decide(&Error::Timeout, 7), Decision::Retry(Duration::from_secs(600)));
+        assert_eq!(patient.decide(&Error::Timeout, 99), Decision::Retry(Duration::from_secs(600)));
+
+        // Retry-After is honored
+        assert_eq!(policy.decide(&Error::RateLimited { retry_after: Some(120) }, 1), Decision::Retry(Duration::from_secs(120)));
+        assert_eq!(policy.decide(&Error::RateLimited { retry_after: Some(1) }, 1), Decision::Retry(Duration::from_secs(10)));
+        assert_eq!(policy.decide(&Error::RateLimited { retry_after: Some(3600) }, 1), Decision::MarkStale);
+        assert_eq!(policy.decide(&Error::RateLimited { retry_after: None }, 2), Decision::Retry(Duration::from_secs(20)));
+    }
+}
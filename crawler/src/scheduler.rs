@@ -0,0 +1,158 @@
+use crate::store::GiveawayStore;
+use format::giveaway::SearchResult;
+
+/// Finished giveaways get one last update, once their winners are likely to be announced.
+const FINAL_SNAPSHOT_DELAY: u64 = 24 * 3600;
+
+/// Final snapshots are more important than regular updates, as they can only be taken once.
+const FINAL_SNAPSHOT_WEIGHT: f64 = 100.0;
+
+/// Failing finished giveaways are given up after this number of failed updates, as they are often broken for good.
+const FINAL_SNAPSHOT_ATTEMPTS: usize = 3;
+
+/// Get the number of entries per hour, according to the two most recent entry counts.
+fn entry_velocity(giveaway: &SearchResult) -> f64 {
+    let mut entry_evolution = match &giveaway.entry_evolution {
+        Some(entry_evolution) => entry_evolution
+            .iter()
+            .filter_map(|(timestamp, entries)| timestamp.parse::<u64>().ok().map(|t| (t, *entries)))
+            .collect::<Vec<(u64, usize)>>(),
+        None => return 0.0,
+    };
+    if entry_evolution.len() < 2 {
+        return 0.0;
+    }
+    entry_evolution.sort_unstable();
+
+    let (t1, e1) = entry_evolution[entry_evolution.len() - 2];
+    let (t2, e2) = entry_evolution[entry_evolution.len() - 1];
+    if t2 <= t1 || e2 <= e1 {
+        return 0.0;
+    }
+    (e2 - e1) as f64 / ((t2 - t1) as f64 / 3600.0)
+}
+
+/// Compute how much a giveaway needs to be updated at the time `now`.
+/// Returns `None` if the giveaway must not be updated anymore.
+///
+/// Running giveaways are weighted by the time since their last update, increased when they are about to end and when they get a lot of entries.
+/// A failed update counts as an update for the weight, so that failing giveaways do not monopolize the crawler.
+/// A failed final snapshot is retried like a regular update, until `FINAL_SNAPSHOT_ATTEMPTS` updates failed.
+pub fn priority(giveaway: &SearchResult, now: u64) -> Option<f64> {
+    let ends_at = giveaway.ends_at();
+    let last_attempt = giveaway.failed_at.unwrap_or(0).max(giveaway.last_updated);
+    let staleness = now.saturating_sub(last_attempt) as f64 / 3600.0;
+
+    if ends_at <= now {
+        let snapshot_at = ends_at.saturating_add(FINAL_SNAPSHOT_DELAY);
+        if now < snapshot_at || giveaway.last_updated >= snapshot_at || giveaway.failures >= FINAL_SNAPSHOT_ATTEMPTS {
+            return None;
+        }
+        if giveaway.failures > 0 {
+            return Some(staleness);
+        }
+        return Some((staleness + 1.0) * FINAL_SNAPSHOT_WEIGHT);
+    }
+
+    let hours_left = (ends_at - now) as f64 / 3600.0;
+    let urgency = 1.0 + 24.0 / (hours_left + 1.0);
+    let popularity = 1.0 + (1.0 + entry_velocity(giveaway)).ln();
+    Some(staleness * urgency * popularity)
+}
+
+/// Get the keys of the `count` giveaways which need to be updated the most.
+pub fn select(giveaways: &dyn GiveawayStore, count: usize, now: u64) -> Vec<String> {
+    let mut candidates = giveaways
+        .iter_all()
+        .filter_map(|g| priority(&g, now).map(|priority| (priority, g.giveaway.campaign.key)))
+        .filter(|(priority, _key)| *priority > 0.0)
+        .collect::<Vec<(f64, String)>>();
+    candidates.sort_by(|(p1, _), (p2, _)| p2.partial_cmp(p1).unwrap_or(std::cmp::Ordering::Equal));
+    candidates.into_iter().take(count).map(|(_priority, key)| key).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{MemoryStore, tests::giveaway};
+
+    const HOUR: u64 = 3600;
+    const NOW: u64 = 1000 * HOUR;
+
+    #[test]
+    fn velocity() {
+        let mut g = giveaway("abcde", NOW, NOW + HOUR);
+        assert_eq!(entry_velocity(&g), 0.0);
+
+        let entry_evolution = g.entry_evolution.as_mut().unwrap();
+        entry_evolution.insert((NOW - 10 * HOUR).to_string(), 10);
+        entry_evolution.insert((NOW - 2 * HOUR).to_string(), 22);
+        assert_eq!(entry_velocity(&g), 10.0);
+    }
+
+    #[test]
+    fn priorities() {
+        // Giveaways ending soon come first
+        let ending_soon = priority(&giveaway("abcde", NOW - HOUR, NOW + HOUR), NOW).unwrap();
+        let ending_later = priority(&giveaway("fghij", NOW - HOUR, NOW + 100 * HOUR), NOW).unwrap();
+        assert!(ending_soon > ending_later);
+
+        // Stale giveaways come first
+        let stale = priority(&giveaway("abcde", NOW - 10 * HOUR, NOW + 100 * HOUR), NOW).unwrap();
+        assert!(stale > ending_later);
+        assert_eq!(priority(&giveaway("abcde", NOW, NOW + 100 * HOUR), NOW), Some(0.0));
+
+        // Popular giveaways come first
+        let mut popular = giveaway("klmno", NOW - HOUR, NOW + 100 * HOUR);
+        popular.entry_evolution.as_mut().unwrap().insert((NOW - 2 * HOUR).to_string(), 2);
+        assert!(priority(&popular, NOW).unwrap() > ending_later);
+
+        // Finished giveaways get a single snapshot, after the delay
+        assert_eq!(priority(&giveaway("abcde", NOW - 10 * HOUR, NOW - HOUR), NOW), None);
+        let snapshot_time = NOW + FINAL_SNAPSHOT_DELAY;
+        assert!(priority(&giveaway("abcde", NOW - 10 * HOUR, NOW - HOUR), snapshot_time).unwrap() > ending_soon);
+        assert_eq!(priority(&giveaway("abcde", snapshot_time, NOW - HOUR), snapshot_time + HOUR), None);
+
+        // Failed updates are retried later
+        let mut failed = giveaway("abcde", NOW - 10 * HOUR, NOW + 100 * HOUR);
+        failed.failed_at = Some(NOW);
+        failed.failures = 1;
+        assert_eq!(priority(&failed, NOW), Some(0.0));
+        assert!(priority(&failed, NOW + HOUR).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn failing_final_snapshot() {
+        let snapshot_time = NOW + FINAL_SNAPSHOT_DELAY;
+        let mut failing = giveaway("abcde", NOW - 10 * HOUR, NOW - HOUR);
+
+        // The giveaway is updated every hour and fails every time, but it does not delay giveaways about to end
+        for hour in 0..10 {
+            let now = snapshot_time + hour * HOUR;
+            let ending_soon = priority(&giveaway("fghij", now - HOUR, now + HOUR), now).unwrap();
+            match priority(&failing, now) {
+                Some(priority) if failing.failures == 0 => assert!(priority > ending_soon),
+                Some(priority) => assert!(priority < ending_soon),
+                None => continue,
+            }
+            failing.failed_at = Some(now);
+            failing.failures += 1;
+        }
+        assert_eq!(failing.failures, FINAL_SNAPSHOT_ATTEMPTS);
+    }
+
+    #[test]
+    fn selecting() {
+        let mut giveaways = MemoryStore::new();
+        giveaways.upsert(giveaway("abcde", NOW - HOUR, NOW + 100 * HOUR));
+        giveaways.upsert(giveaway("fghij", NOW - HOUR, NOW + 2 * HOUR));
+        giveaways.upsert(giveaway("klmno", NOW - 5 * HOUR, NOW + 100 * HOUR));
+        giveaways.upsert(giveaway("pqrst", NOW - 50 * HOUR, NOW - 40 * HOUR));
+        giveaways.upsert(giveaway("uvwxy", NOW - 50 * HOUR, NOW - HOUR));
+        giveaways.upsert(giveaway("zzzzz", NOW, NOW + 100 * HOUR));
+
+        assert_eq!(select(&giveaways, 3, NOW), vec!["pqrst", "fghij", "klmno"]);
+        assert_eq!(select(&giveaways, 10, NOW).len(), 4);
+        assert!(select(&giveaways, 0, NOW).is_empty());
+    }
+}
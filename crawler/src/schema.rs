@@ -0,0 +1,362 @@
+use crate::{archive::{archived_keys, list_snapshots, read_snapshot}, config::Config, fetch::*, gleam, quarantine::quarantined_pages, ratelimit::HostRateLimiter, store::open_store};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::{collections::{BTreeMap, BTreeSet}, fs::File, io::prelude::*, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tokio::task;
+
+/// The number of campaign keys kept as examples for each type of a field.
+const MAX_EXAMPLES: usize = 3;
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TypeStats {
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FieldStats {
+    /// The JSON types of the values of the field
+    pub types: BTreeMap<String, TypeStats>,
+}
+
+impl FieldStats {
+    fn count(&self) -> usize {
+        self.types.values().map(|t| t.count).sum()
+    }
+
+    fn examples<'a>(&'a self, types: impl Iterator<Item = &'a String>) -> Vec<String> {
+        let mut examples: Vec<String> = Vec::new();
+        for example in types.filter_map(|t| self.types.get(t)).flat_map(|t| t.examples.iter()) {
+            if !examples.contains(example) {
+                examples.push(example.clone());
+            }
+        }
+        examples.truncate(MAX_EXAMPLES);
+        examples
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StructStats {
+    /// The number of objects observed
+    pub objects: usize,
+    pub fields: BTreeMap<String, FieldStats>,
+}
+
+impl StructStats {
+    fn frequency(&self, field: &FieldStats) -> f64 {
+        field.count() as f64 / self.objects.max(1) as f64
+    }
+}
+
+/// The fields of the JSON data of gleam.io pages, by struct.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Schema {
+    pub pages: usize,
+    pub structs: BTreeMap<String, StructStats>,
+}
+
+impl Schema {
+    fn observe_object(&mut self, name: &str, key: &str, object: &Value) {
+        let object = match object.as_object() {
+            Some(object) => object,
+            None => return,
+        };
+
+        let stats = self.structs.entry(name.to_string()).or_default();
+        stats.objects += 1;
+        for (field, value) in object {
+            let type_stats = stats.fields.entry(field.clone()).or_default().types.entry(type_name(value).to_string()).or_default();
+            type_stats.count += 1;
+            if type_stats.examples.len() < MAX_EXAMPLES && !type_stats.examples.iter().any(|example| example == key) {
+                type_stats.examples.push(key.to_string());
+            }
+        }
+    }
+
+    /// Record the fields of a page.
+    /// Archived snapshots do not contain the contestant data.
+    pub fn observe(&mut self, giveaway: &Value, init_contestant: Option<&Value>) {
+        let key = giveaway.pointer("/campaign/key").and_then(|key| key.as_str()).unwrap_or("unknown");
+        self.pages += 1;
+        self.observe_object("Campaign", key, &giveaway["campaign"]);
+        for entry_method in giveaway["entry_methods"].as_array().into_iter().flatten() {
+            self.observe_object("EntryMethod", key, entry_method);
+        }
+        if let Some(init_contestant) = init_contestant {
+            self.observe_object("InitContestant", key, init_contestant);
+        }
+    }
+
+    /// Compare this schema with a baseline.
+    /// Structs which were not observed at all are ignored.
+    pub fn drifts(&self, baseline: &Schema) -> Vec<Drift> {
+        let mut drifts = Vec::new();
+        for (name, stats) in &self.structs {
+            let old_stats = match baseline.structs.get(name) {
+                Some(old_stats) => old_stats,
+                None => continue,
+            };
+
+            for (field, field_stats) in &stats.fields {
+                let old_field_stats = match old_stats.fields.get(field) {
+                    Some(old_field_stats) => old_field_stats,
+                    None => {
+                        drifts.push(Drift::Appeared {
+                            field: format!("{}.{}", name, field),
+                            frequency: stats.frequency(field_stats),
+                            examples: field_stats.examples(field_stats.types.keys()),
+                        });
+                        continue;
+                    }
+                };
+
+                let old_types = old_field_stats.types.keys().cloned().collect::<BTreeSet<String>>();
+                let new_types = field_stats.types.keys().cloned().collect::<BTreeSet<String>>();
+                if old_types != new_types {
+                    drifts.push(Drift::TypeChanged {
+                        field: format!("{}.{}", name, field),
+                        examples: field_stats.examples(new_types.difference(&old_types).chain(new_types.iter())),
+                        old_types: old_types.into_iter().collect(),
+                        new_types: new_types.into_iter().collect(),
+                    });
+                }
+            }
+
+            for (field, old_field_stats) in &old_stats.fields {
+                if !stats.fields.contains_key(field) {
+                    drifts.push(Drift::Disappeared {
+                        field: format!("{}.{}", name, field),
+                        frequency: old_stats.frequency(old_field_stats),
+                    });
+                }
+            }
+        }
+        drifts
+    }
+
+    pub fn load(path: &str) -> Option<Schema> {
+        let file = File::open(path).ok()?;
+        match serde_json::from_reader(file) {
+            Ok(schema) => Some(schema),
+            Err(e) => {
+                eprintln!("The schema file {} is unreadable: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let data = serde_json::to_string_pretty(self).expect("Can't serialize schema");
+        let mut file = File::create(path).expect("Can't open the schema file");
+        file.write_all(data.as_bytes()).expect("Can't write the schema file");
+    }
+}
+
+/// A change of the data sent by gleam.io.
+#[derive(Debug, PartialEq)]
+pub enum Drift {
+    Appeared { field: String, frequency: f64, examples: Vec<String> },
+    Disappeared { field: String, frequency: f64 },
+    TypeChanged { field: String, old_types: Vec<String>, new_types: Vec<String>, examples: Vec<String> },
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Appeared { field, frequency, examples } => write!(f, "+ {} appeared in {:.0}% of the objects (e.g. {})", field, frequency * 100.0, examples.join(", ")),
+            Drift::Disappeared { field, frequency } => write!(f, "- {} disappeared (was in {:.0}% of the objects)", field, frequency * 100.0),
+            Drift::TypeChanged { field, old_types, new_types, examples } => write!(f, "~ {} changed type: {} -> {} (e.g. {})", field, old_types.join(" | "), new_types.join(" | "), examples.join(", ")),
+        }
+    }
+}
+
+/// Load the complete data of the campaigns: the last archived snapshot of each campaign, or the pages of the running giveaways if `fetch` is set.
+/// Pages which could not be parsed are never archived, so the quarantined ones are used too when they are more recent.
+/// Archived snapshots do not contain the contestant data.
+pub async fn load_campaigns(config: &Config, fetch: bool) -> Vec<(Value, Option<Value>)> {
+    let mut campaigns = Vec::new();
+
+    if fetch {
+        std::env::set_var("MINREQ_TIMEOUT", config.timeout.to_string());
+        let fetcher: Arc<dyn Fetcher> = Arc::new(MinreqFetcher);
+        let limiter = HostRateLimiter::new(Duration::from_secs(config.cooldown as u64), 1);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let urls = open_store(config).iter_running(now).map(|giveaway| giveaway.get_url()).collect::<Vec<String>>();
+        println!("Loading {} pages...", urls.len());
+        for url in urls {
+            limiter.acquire("gleam.io").await;
+            let fetcher = Arc::clone(&fetcher);
+            let page = task::spawn_blocking(move || gleam::fetch_raw(fetcher.as_ref(), &url).map_err(|e| (url, e))).await.expect("Failed to join the fetching task");
+            match page {
+                Ok((giveaway, init_contestant)) => campaigns.push((giveaway, Some(init_contestant))),
+                Err((url, e)) => eprintln!("Failed to load {}: {}", url, e),
+            }
+        }
+    } else {
+        let archive_config = match &config.archive {
+            Some(archive_config) => archive_config,
+            None => {
+                eprintln!("The archive must be enabled in the configuration file, or pages must be loaded with --fetch.");
+                std::process::exit(1);
+            }
+        };
+        let mut latest: BTreeMap<String, (u64, Value, Option<Value>)> = BTreeMap::new();
+        for key in archived_keys(&archive_config.folder) {
+            let last = match list_snapshots(&archive_config.folder, &key).pop() {
+                Some(last) => last,
+                None => continue,
+            };
+            match read_snapshot(&archive_config.folder, &key, last) {
+                Ok(snapshot) => {
+                    latest.insert(key, (last, snapshot.giveaway, None));
+                }
+                Err(e) => eprintln!("Can't read the last snapshot of {}: {}", key, e),
+            }
+        }
+
+        if let Some(folder) = &config.quarantine_folder {
+            let pages = quarantined_pages(folder).unwrap_or_else(|e| {
+                eprintln!("Can't read the quarantine: {}", e);
+                Vec::new()
+            });
+            for (key, quarantined_at, html) in pages {
+                if matches!(latest.get(&key), Some((last, _, _)) if *last >= quarantined_at) {
+                    continue;
+                }
+                match format::parsing::parse_raw_html(&html) {
+                    Ok((giveaway, init_contestant)) => {
+                        latest.insert(key, (quarantined_at, giveaway, Some(init_contestant)));
+                    }
+                    Err(e) => eprintln!("Can't read the quarantined page of {}: {}", key, e),
+                }
+            }
+        }
+
+        campaigns.extend(latest.into_iter().map(|(_key, (_fetched_at, giveaway, init_contestant))| (giveaway, init_contestant)));
+    }
+
+    campaigns
+}
+
+/// Observe the archived snapshots, or the pages of the running giveaways if `fetch` is set, and compare them with the baseline.
+pub async fn check_schema(config: &Config, fetch: bool, save: bool) {
+    let mut schema = Schema::default();
+    for (giveaway, init_contestant) in load_campaigns(config, fetch).await {
+        schema.observe(&giveaway, init_contestant.as_ref());
+    }
+    println!("{} pages observed", schema.pages);
+
+    match Schema::load(&config.schema_file) {
+        Some(baseline) => {
+            let drifts = schema.drifts(&baseline);
+            if drifts.is_empty() {
+                println!("The schema did not change since the baseline ({} pages)", baseline.pages);
+            }
+            for drift in drifts {
+                println!("{}", drift);
+            }
+        }
+        None if !save => println!("There is no baseline yet. Run this command with --save to create it."),
+        None => (),
+    }
+
+    if save {
+        schema.save(&config.schema_file);
+        println!("The baseline has been saved to {}", config.schema_file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{Snapshot, save_snapshot};
+    use serde_json::json;
+
+    #[test]
+    fn drifts() {
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let (giveaway, init_contestant) = gleam::fetch_raw(&fetcher, "https://gleam.io/7qHd6/-").unwrap();
+        let mut baseline = Schema::default();
+        baseline.observe(&giveaway, Some(&init_contestant));
+        assert!(baseline.drifts(&baseline).is_empty());
+
+        // Two campaigns, one with new fields
+        let mut schema = Schema::default();
+        schema.observe(&giveaway, Some(&init_contestant));
+        let (drifted, _) = gleam::fetch_raw(&fetcher, "https://gleam.io/drft1/-").unwrap();
+        schema.observe(&drifted, None);
+        assert_eq!(schema.drifts(&baseline), vec![
+            Drift::Appeared { field: String::from("Campaign.require_captcha"), frequency: 0.5, examples: vec![String::from("drft1")] },
+            Drift::Appeared { field: String::from("EntryMethod.streak"), frequency: 0.5, examples: vec![String::from("drft1")] },
+        ]);
+
+        // Disappeared fields and type changes
+        let mut baseline = Schema::default();
+        baseline.observe(&json!({"campaign": {"key": "abcde", "entry_limit": null, "shortener": {}}}), None);
+        let mut schema = Schema::default();
+        schema.observe(&json!({"campaign": {"key": "fghij", "entry_limit": "10"}}), None);
+        schema.observe(&json!({"campaign": {"key": "klmno", "entry_limit": null}}), None);
+        let drifts = schema.drifts(&baseline);
+        assert_eq!(drifts, vec![
+            Drift::TypeChanged {
+                field: String::from("Campaign.entry_limit"),
+                old_types: vec![String::from("null")],
+                new_types: vec![String::from("null"), String::from("string")],
+                examples: vec![String::from("fghij"), String::from("klmno")],
+            },
+            Drift::Disappeared { field: String::from("Campaign.shortener"), frequency: 1.0 },
+        ]);
+        assert_eq!(drifts[1].to_string(), "- Campaign.shortener disappeared (was in 100% of the objects)");
+    }
+
+    #[tokio::test]
+    async fn archived_campaigns() {
+        let folder = std::env::temp_dir().join(format!("gleam_finder_schema_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        let quarantine_folder = folder.join("quarantine").to_str().unwrap().to_string();
+        let mut config: Config = toml::from_str(&format!("[archive]\nfolder = {:?}", folder.join("archive").to_str().unwrap())).unwrap();
+        config.quarantine_folder = Some(quarantine_folder.clone());
+
+        let fetcher = FixtureFetcher::new("test_data/pages");
+        let (giveaway, init_contestant) = gleam::fetch_raw(&fetcher, "https://gleam.io/7qHd6/-").unwrap();
+        let mut baseline = Schema::default();
+        baseline.observe(&giveaway, Some(&init_contestant));
+
+        // The campaign is archived as gleam.io sent it, without the winners field
+        let (nownr, snapshot) = gleam::fetch(&fetcher, "https://gleam.io/nownr/-", &config.parse_options()).unwrap();
+        assert!(save_snapshot(config.archive.as_ref().unwrap(), &Snapshot::new(&nownr, snapshot)));
+        let mut schema = Schema::default();
+        for (giveaway, init_contestant) in load_campaigns(&config, false).await {
+            schema.observe(&giveaway, init_contestant.as_ref());
+        }
+        assert_eq!(schema.drifts(&baseline), vec![Drift::Disappeared { field: String::from("Campaign.winners"), frequency: 1.0 }]);
+
+        // A page which can't be parsed because of a type change is never archived, but it is quarantined
+        assert!(gleam::fetch(&fetcher, "https://gleam.io/brkn1/-", &config.parse_options()).is_err());
+        let mut schema = Schema::default();
+        for (giveaway, init_contestant) in load_campaigns(&config, false).await {
+            schema.observe(&giveaway, init_contestant.as_ref());
+        }
+        assert_eq!(schema.pages, 2);
+        assert!(schema.drifts(&baseline).contains(&Drift::TypeChanged {
+            field: String::from("EntryMethod.worth"),
+            old_types: vec![String::from("number")],
+            new_types: vec![String::from("number"), String::from("string")],
+            examples: vec![String::from("brkn1"), String::from("nownr")],
+        }));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
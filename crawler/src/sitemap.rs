@@ -0,0 +1,297 @@
+use crate::{fetch::{Fetcher, fetch_limited}, ratelimit::HostRateLimiter, sources::Source};
+use chrono::{DateTime, NaiveDate};
+use flate2::read::GzDecoder;
+use futures::future::{LocalBoxFuture, FutureExt};
+use std::{collections::HashMap, fs::File, io::prelude::*, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use string_tools::{get_all_after_strict, get_all_before_strict, get_all_between_strict};
+
+/// Sitemap indexes can reference other indexes, but we do not want to follow them forever.
+const MAX_DEPTH: usize = 3;
+
+/// Discover pages by reading the sitemaps of known sites.
+/// Only pages modified since the last run are returned.
+pub struct SitemapSource {
+    fetcher: Arc<dyn Fetcher>,
+    limiter: Arc<HostRateLimiter>,
+    sites: Vec<String>,
+    state_file: String,
+}
+
+impl SitemapSource {
+    pub fn new(fetcher: Arc<dyn Fetcher>, limiter: Arc<HostRateLimiter>, sites: Vec<String>, state_file: String) -> SitemapSource {
+        SitemapSource {
+            fetcher,
+            limiter,
+            sites,
+            state_file,
+        }
+    }
+
+    /// Read the timestamps of the last run on each site.
+    /// A corrupted state is ignored, which means that every site will be read entirely.
+    fn read_state(&self) -> HashMap<String, u64> {
+        let mut content = String::new();
+        match File::open(&self.state_file) {
+            Ok(mut file) => {
+                if let Err(e) = file.read_to_string(&mut content) {
+                    eprintln!("Failed to read the sitemap state: {}", e);
+                    return HashMap::new();
+                }
+                match serde_json::from_str(&content) {
+                    Ok(state) => state,
+                    Err(e) => {
+                        eprintln!("Failed to parse the sitemap state, starting from scratch: {}", e);
+                        HashMap::new()
+                    }
+                }
+            }
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    fn save_state(&self, state: &HashMap<String, u64>) {
+        let mut file = File::create(&self.state_file).expect("Can't open sitemap state file");
+        let data = serde_json::to_string(state).expect("Can't serialize sitemap state");
+        file.write_all(data.as_bytes()).expect("Can't write sitemap state");
+    }
+
+    /// Load a sitemap and collect the pages modified after `since`.
+    /// Sitemap indexes are followed recursively.
+    /// Fails if one of the sitemaps could not be loaded, in which case some pages may be missing from `results`.
+    /// The future is boxed because it is recursive.
+    fn load<'a>(&'a self, url: &'a str, since: u64, depth: usize, results: &'a mut Vec<String>) -> LocalBoxFuture<'a, Result<(), Error>> {
+        async move {
+            let sitemap_url = url.to_string();
+            let sitemap = match fetch_limited(&self.fetcher, &self.limiter, url, move |fetcher| fetch_sitemap(fetcher, &sitemap_url)).await {
+                Ok(sitemap) => sitemap,
+                Err(e) => {
+                    eprintln!("Failed to load the sitemap {}: {}", url, e);
+                    return Err(e);
+                }
+            };
+
+            match parse_sitemap(&sitemap, since) {
+                Sitemap::Index(sitemaps) => {
+                    if depth >= MAX_DEPTH {
+                        eprintln!("Sitemap index {} is too deep, ignoring it", url);
+                        return Ok(());
+                    }
+                    let mut result = Ok(());
+                    for sitemap in sitemaps {
+                        if let Err(e) = self.load(&sitemap, since, depth + 1, results).await {
+                            result = Err(e);
+                        }
+                    }
+                    result
+                }
+                Sitemap::Pages(pages) => {
+                    for page in pages {
+                        if !results.contains(&page) {
+                            results.push(page);
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        }.boxed_local()
+    }
+}
+
+impl Source for SitemapSource {
+    fn name(&self) -> &'static str {
+        "sitemaps"
+    }
+
+    fn discover(&mut self) -> LocalBoxFuture<'_, Vec<String>> {
+        async move {
+            let mut state = self.read_state();
+            let mut results = Vec::new();
+
+            for site in &self.sites {
+                let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                let since = state.get(site).copied().unwrap_or(0);
+                let url = format!("{}/sitemap.xml", site.trim_end_matches('/'));
+
+                // Pages of a site which failed to load will be looked for again on the next run
+                if self.load(&url, since, 0, &mut results).await.is_ok() {
+                    state.insert(site.clone(), start);
+                }
+            }
+
+            self.save_state(&state);
+            results
+        }.boxed_local()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// A sitemap index, containing the urls of other sitemaps
+    Index(Vec<String>),
+    /// A regular sitemap, containing the urls of pages
+    Pages(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NetworkError(minreq::Error),
+    DecompressionError(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NetworkError(e) => write!(f, "{}", e),
+            Error::DecompressionError(e) => write!(f, "invalid gzip file: {}", e),
+        }
+    }
+}
+
+fn fetch_sitemap(fetcher: &dyn Fetcher, url: &str) -> Result<String, Error> {
+    let response = fetcher.get(url, &[
+        ("Accept", "application/xml,text/xml,application/x-gzip"),
+        ("User-Agent", "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:78.0) Gecko/20100101 Firefox/78.0"),
+    ]).map_err(Error::NetworkError)?;
+
+    decode(response.as_bytes())
+}
+
+/// Decompress gzipped sitemaps (they can be served with a `.xml.gz` url or with the wrong content type).
+fn decode(body: &[u8]) -> Result<String, Error> {
+    if body.starts_with(&[0x1f, 0x8b]) {
+        let mut content = String::new();
+        GzDecoder::new(body).read_to_string(&mut content).map_err(Error::DecompressionError)?;
+        Ok(content)
+    } else {
+        Ok(String::from_utf8_lossy(body).into_owned())
+    }
+}
+
+/// Parse a W3C datetime (as used in sitemaps) to a timestamp.
+fn parse_lastmod(lastmod: &str) -> Option<u64> {
+    let lastmod = lastmod.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(lastmod) {
+        return Some(date.timestamp() as u64);
+    }
+    if let Ok(date) = DateTime::parse_from_str(lastmod, "%Y-%m-%dT%H:%M%:z") {
+        return Some(date.timestamp() as u64);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(lastmod, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|date| date.timestamp() as u64);
+    }
+    None
+}
+
+/// Get the content of every `tag` element.
+fn elements<'a>(mut xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let opening = format!("<{}>", tag);
+    let closing = format!("</{}>", tag);
+    let mut elements = Vec::new();
+
+    while let Some(rest) = get_all_after_strict(xml, &opening) {
+        match get_all_before_strict(rest, &closing) {
+            Some(content) => {
+                elements.push(content);
+                xml = &rest[content.len() + closing.len()..];
+            }
+            None => break,
+        }
+    }
+
+    elements
+}
+
+/// Read a sitemap, keeping only the entries modified after `since`.
+/// Entries without modification date are only kept on the first run (when `since` is 0).
+pub fn parse_sitemap(xml: &str, since: u64) -> Sitemap {
+    let is_index = xml.contains("<sitemapindex");
+    let tag = if is_index { "sitemap" } else { "url" };
+
+    let mut urls = Vec::new();
+    for entry in elements(xml, tag) {
+        let loc = match get_all_between_strict(entry, "<loc>", "</loc>") {
+            Some(loc) => loc.trim().replace("&amp;", "&"),
+            None => continue,
+        };
+
+        let modified = get_all_between_strict(entry, "<lastmod>", "</lastmod>").and_then(parse_lastmod);
+        let keep = match modified {
+            Some(modified) => modified > since,
+            None => since == 0,
+        };
+
+        if keep && !urls.contains(&loc) {
+            urls.push(loc);
+        }
+    }
+
+    if is_index {
+        Sitemap::Index(urls)
+    } else {
+        Sitemap::Pages(urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lastmod() {
+        assert_eq!(parse_lastmod("2020-10-12"), Some(1602460800));
+        assert_eq!(parse_lastmod("2020-10-12T18:30:02Z"), Some(1602527402));
+        assert_eq!(parse_lastmod("2020-10-12T20:30:02+02:00"), Some(1602527402));
+        assert_eq!(parse_lastmod("2020-10-12T20:30+02:00"), Some(1602527400));
+        assert_eq!(parse_lastmod("yesterday"), None);
+    }
+
+    #[test]
+    fn pages() {
+        let sitemap = include_str!("../test_data/sitemaps/sitemap.xml");
+        assert_eq!(parse_sitemap(sitemap, 0), Sitemap::Pages(vec![
+            String::from("https://aggregator.example.com/"),
+            String::from("https://aggregator.example.com/giveaways/gaming-pc"),
+            String::from("https://aggregator.example.com/giveaways/headphones"),
+            String::from("https://aggregator.example.com/giveaways/steam-keys?page=2&sort=new"),
+        ]));
+
+        // 2020-10-11
+        assert_eq!(parse_sitemap(sitemap, 1602374400), Sitemap::Pages(vec![
+            String::from("https://aggregator.example.com/giveaways/gaming-pc"),
+            String::from("https://aggregator.example.com/giveaways/steam-keys?page=2&sort=new"),
+        ]));
+    }
+
+    #[test]
+    fn index() {
+        let sitemap = include_str!("../test_data/sitemaps/sitemap_index.xml");
+        assert_eq!(parse_sitemap(sitemap, 1602374400), Sitemap::Index(vec![
+            String::from("https://aggregator.example.com/sitemap-giveaways.xml.gz"),
+        ]));
+    }
+
+    #[tokio::test]
+    async fn state() {
+        let state_file = std::env::temp_dir().join(format!("gleam_finder_sitemaps_{}.json", std::process::id()));
+        std::fs::write(&state_file, r#"{"https://shop.example.com": 16024"#).unwrap();
+        let fetcher: Arc<dyn Fetcher> = Arc::new(crate::fetch::FixtureFetcher::new("test_data/sitemaps"));
+        let limiter = Arc::new(HostRateLimiter::new(std::time::Duration::from_secs(0), 1));
+        let sites = vec![String::from("https://aggregator.example.com"), String::from("https://shop.example.com")];
+        let mut source = SitemapSource::new(fetcher, limiter, sites, state_file.to_str().unwrap().to_string());
+
+        // The truncated state is ignored, and the sitemap of giveaways of the aggregator is missing
+        assert_eq!(source.discover().await.len(), 4);
+        let state = source.read_state();
+        assert!(state.contains_key("https://shop.example.com"));
+        assert!(!state.contains_key("https://aggregator.example.com"));
+
+        std::fs::remove_file(&state_file).unwrap();
+    }
+
+    #[test]
+    fn gzip() {
+        let sitemap = decode(include_bytes!("../test_data/sitemaps/sitemap.xml.gz")).unwrap();
+        assert_eq!(sitemap, include_str!("../test_data/sitemaps/sitemap.xml"));
+        assert_eq!(decode(b"<urlset></urlset>").unwrap(), "<urlset></urlset>");
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{config::*, fetch::Fetcher, google::GoogleSource, feeds::FeedSource, sitemap::SitemapSource, ratelimit::HostRateLimiter};
+use futures::future::{self, LocalBoxFuture, FutureExt};
+use std::sync::Arc;
+
+/// A discovery channel producing urls of pages which may contain gleam.io links.
+/// Pages are then loaded by `crawler::resolve`.
+/// Requests go through the rate limiter shared with the rest of the crawler.
+pub trait Source {
+    fn name(&self) -> &'static str;
+
+    fn discover(&mut self) -> LocalBoxFuture<'_, Vec<String>>;
+}
+
+/// A fixed list of pages, set in the configuration file.
+pub struct SeedSource {
+    urls: Vec<String>,
+}
+
+impl SeedSource {
+    pub fn new(urls: Vec<String>) -> SeedSource {
+        SeedSource { urls }
+    }
+}
+
+impl Source for SeedSource {
+    fn name(&self) -> &'static str {
+        "seeds"
+    }
+
+    fn discover(&mut self) -> LocalBoxFuture<'_, Vec<String>> {
+        future::ready(self.urls.clone()).boxed_local()
+    }
+}
+
+/// Build the sources enabled in the configuration file.
+pub fn build_sources(config: &Config, fetcher: &Arc<dyn Fetcher>, limiter: &Arc<HostRateLimiter>) -> Vec<Box<dyn Source>> {
+    let mut sources: Vec<Box<dyn Source>> = Vec::new();
+
+    if let Some(google) = &config.sources.google {
+        if google.enabled {
+            sources.push(Box::new(GoogleSource::new(Arc::clone(fetcher), Arc::clone(limiter))));
+        }
+    }
+
+    if let Some(seeds) = &config.sources.seeds {
+        if seeds.enabled {
+            sources.push(Box::new(SeedSource::new(seeds.urls.clone())));
+        }
+    }
+
+    if let Some(feeds) = &config.sources.feeds {
+        if feeds.enabled {
+            sources.push(Box::new(FeedSource::new(Arc::clone(fetcher), Arc::clone(limiter), feeds.urls.clone())));
+        }
+    }
+
+    if let Some(sitemaps) = &config.sources.sitemaps {
+        if sitemaps.enabled {
+            sources.push(Box::new(SitemapSource::new(Arc::clone(fetcher), Arc::clone(limiter), sitemaps.sites.clone(), sitemaps.state_file.clone())));
+        }
+    }
+
+    sources
+}
+
+/// Run every source and merge their results, without duplicates.
+pub async fn discover(sources: &mut [Box<dyn Source>]) -> Vec<String> {
+    let mut results = Vec::new();
+    for source in sources.iter_mut() {
+        let urls = source.discover().await;
+        println!("{} pages discovered by the {} source", urls.len(), source.name());
+        for url in urls {
+            if !results.contains(&url) {
+                results.push(url);
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn merge_sources() {
+        let mut sources: Vec<Box<dyn Source>> = vec![
+            Box::new(SeedSource::new(vec![String::from("https://a.com/1"), String::from("https://b.com/2")])),
+            Box::new(SeedSource::new(vec![String::from("https://b.com/2"), String::from("https://c.com/3")])),
+        ];
+
+        assert_eq!(
+            discover(&mut sources).await,
+            vec!["https://a.com/1", "https://b.com/2", "https://c.com/3"]
+        );
+    }
+}
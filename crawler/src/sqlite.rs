@@ -0,0 +1,262 @@
+use crate::{config::BackupConfig, database::{recover_from_backups, set_aside, Error}, store::{GiveawayStore, Tombstone}};
+use format::giveaway::SearchResult;
+use rusqlite::{ffi, params, Connection, ToSql, NO_PARAMS};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS giveaways (
+        key TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        starts_at INTEGER NOT NULL,
+        ends_at INTEGER NOT NULL,
+        last_updated INTEGER NOT NULL,
+        entry_count INTEGER,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS giveaways_last_updated ON giveaways (last_updated);
+    CREATE INDEX IF NOT EXISTS giveaways_ends_at ON giveaways (ends_at);
+    CREATE TABLE IF NOT EXISTS referers (
+        key TEXT NOT NULL REFERENCES giveaways (key) ON DELETE CASCADE,
+        url TEXT NOT NULL,
+        PRIMARY KEY (key, url)
+    );
+    CREATE TABLE IF NOT EXISTS entry_counts (
+        key TEXT NOT NULL REFERENCES giveaways (key) ON DELETE CASCADE,
+        timestamp INTEGER NOT NULL,
+        entries INTEGER NOT NULL,
+        PRIMARY KEY (key, timestamp)
+    );
+    CREATE TABLE IF NOT EXISTS tombstones (
+        key TEXT PRIMARY KEY NOT NULL,
+        reason TEXT NOT NULL,
+        removed_at INTEGER NOT NULL,
+        ends_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+";
+
+/// Open the database, creating the tables if needed.
+/// Fails if the database is corrupted.
+fn open(path: &str) -> Result<Connection, rusqlite::Error> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    let check: String = connection.query_row("PRAGMA quick_check", NO_PARAMS, |row| row.get(0))?;
+    if check != "ok" {
+        return Err(rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_CORRUPT), Some(check)));
+    }
+    Ok(connection)
+}
+
+/// Insert or replace giveaways.
+/// Referers and entry counts are added to the ones already stored.
+fn save(connection: &mut Connection, giveaways: &[&SearchResult]) {
+    let transaction = connection.transaction().expect("Can't start a database transaction");
+    for giveaway in giveaways {
+        // Referers and entry counts have their own tables
+        let mut data = match serde_json::to_value(giveaway).expect("Can't serialize giveaway") {
+            Value::Object(data) => data,
+            _ => unreachable!(),
+        };
+        data.remove("referers");
+        data.remove("entry_evolution");
+        let key = &giveaway.giveaway.campaign.key;
+
+        transaction.execute(
+            "INSERT INTO giveaways (key, name, starts_at, ends_at, last_updated, entry_count, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (key) DO UPDATE SET name = ?2, starts_at = ?3, ends_at = ?4, last_updated = ?5, entry_count = ?6, data = ?7",
+            params![
+                key,
+                giveaway.get_name(),
+                giveaway.giveaway.campaign.starts_at as i64,
+                giveaway.ends_at() as i64,
+                giveaway.last_updated as i64,
+                giveaway.entry_count.map(|c| c as i64),
+                Value::Object(data).to_string(),
+            ],
+        ).expect("Can't write giveaway to database");
+
+        for referer in &giveaway.referers {
+            transaction.execute(
+                "INSERT OR IGNORE INTO referers (key, url) VALUES (?1, ?2)",
+                params![key, referer],
+            ).expect("Can't write referer to database");
+        }
+
+        for (timestamp, entries) in giveaway.entry_evolution.iter().flatten() {
+            let timestamp: i64 = match timestamp.parse() {
+                Ok(timestamp) => timestamp,
+                Err(_) => continue,
+            };
+            transaction.execute(
+                "INSERT OR REPLACE INTO entry_counts (key, timestamp, entries) VALUES (?1, ?2, ?3)",
+                params![key, timestamp, *entries as i64],
+            ).expect("Can't write entry count to database");
+        }
+    }
+    transaction.commit().expect("Can't commit database transaction");
+}
+
+/// Read giveaways matching a SQL condition on the `giveaways` table.
+fn read_where(connection: &Connection, condition: &str, order: &str, parameters: &[&dyn ToSql]) -> Vec<SearchResult> {
+    let mut referers: HashMap<String, Vec<String>> = HashMap::new();
+    let mut statement = connection.prepare(&format!("SELECT referers.key, url FROM referers JOIN giveaways ON giveaways.key = referers.key WHERE {}", condition)).expect("Can't read referers");
+    let rows = statement.query_map(parameters, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).expect("Can't read referers");
+    for row in rows {
+        let (key, url) = row.expect("Can't read referer");
+        referers.entry(key).or_default().push(url);
+    }
+
+    let mut entry_counts: HashMap<String, Map<String, Value>> = HashMap::new();
+    let mut statement = connection.prepare(&format!("SELECT entry_counts.key, timestamp, entries FROM entry_counts JOIN giveaways ON giveaways.key = entry_counts.key WHERE {}", condition)).expect("Can't read entry counts");
+    let rows = statement.query_map(parameters, |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))).expect("Can't read entry counts");
+    for row in rows {
+        let (key, timestamp, entries) = row.expect("Can't read entry count");
+        entry_counts.entry(key).or_default().insert(timestamp.to_string(), Value::from(entries));
+    }
+
+    let mut giveaways = Vec::new();
+    let mut statement = connection.prepare(&format!("SELECT key, data FROM giveaways WHERE {} ORDER BY {}", condition, order)).expect("Can't read giveaways");
+    let rows = statement.query_map(parameters, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).expect("Can't read giveaways");
+    for row in rows {
+        let (key, data) = row.expect("Can't read giveaway");
+        let mut data: Map<String, Value> = serde_json::from_str(&data).expect("Failed to parse giveaway");
+        data.insert(String::from("referers"), Value::from(referers.remove(&key).unwrap_or_default()));
+        data.insert(String::from("entry_evolution"), entry_counts.remove(&key).map(Value::Object).unwrap_or(Value::Null));
+        giveaways.push(serde_json::from_value(Value::Object(data)).expect("Failed to parse giveaway"));
+    }
+
+    giveaways
+}
+
+/// Read tombstones matching a SQL condition on the `tombstones` table.
+fn read_tombstones_where(connection: &Connection, condition: &str, parameters: &[&dyn ToSql]) -> Vec<Tombstone> {
+    let mut statement = connection.prepare(&format!("SELECT key, reason, removed_at, data FROM tombstones WHERE {} ORDER BY key", condition)).expect("Can't read tombstones");
+    let rows = statement.query_map(parameters, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, String>(3)?))).expect("Can't read tombstones");
+    rows.map(|row| {
+        let (key, reason, removed_at, data) = row.expect("Can't read tombstone");
+        Tombstone {
+            key,
+            reason,
+            removed_at: removed_at as u64,
+            giveaway: serde_json::from_str(&data).expect("Failed to parse buried giveaway"),
+        }
+    }).collect()
+}
+
+/// A store backed by a SQLite database.
+/// Changes are written immediately.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Open the database, or rebuild it from the latest backup if it is corrupted.
+    /// Fails if the database is corrupted and can't be recovered.
+    pub fn open(path: &str, backups: Option<&BackupConfig>) -> Result<SqliteStore, Error> {
+        match open(path) {
+            Ok(connection) => Ok(SqliteStore { connection }),
+            Err(e) => {
+                eprintln!("The database {} is unreadable: {}", path, e);
+                let (giveaways, tombstones) = recover_from_backups(backups)?;
+                set_aside(path);
+                let mut connection = open(path).expect("Can't create database file");
+                save(&mut connection, &giveaways.iter().collect::<Vec<&SearchResult>>());
+                let mut store = SqliteStore { connection };
+                for tombstone in tombstones {
+                    store.insert_tombstone(tombstone);
+                }
+                Ok(store)
+            }
+        }
+    }
+}
+
+impl GiveawayStore for SqliteStore {
+    fn get(&self, key: &str) -> Option<SearchResult> {
+        read_where(&self.connection, "giveaways.key = ?1", "key", &[&key]).pop()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM giveaways WHERE key = ?1", params![key], |row| row.get(0)).expect("Can't read giveaways");
+        count > 0
+    }
+
+    fn upsert(&mut self, giveaway: SearchResult) {
+        let giveaway = match self.get(&giveaway.giveaway.campaign.key) {
+            Some(old_giveaway) => old_giveaway + giveaway,
+            None => giveaway,
+        };
+        save(&mut self.connection, &[&giveaway]);
+    }
+
+    fn delete(&mut self, key: &str) -> bool {
+        let deleted = self.connection.execute("DELETE FROM giveaways WHERE key = ?1", params![key]).expect("Can't delete giveaway from database");
+        deleted > 0
+    }
+
+    fn len(&self) -> usize {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM giveaways", NO_PARAMS, |row| row.get(0)).expect("Can't count giveaways");
+        count as usize
+    }
+
+    fn iter_all(&self) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        Box::new(read_where(&self.connection, "1", "key", NO_PARAMS).into_iter())
+    }
+
+    fn iter_running(&self, now: u64) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        Box::new(read_where(&self.connection, "giveaways.ends_at > ?1", "key", &[&(now as i64)]).into_iter())
+    }
+
+    fn count_running(&self, now: u64) -> usize {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM giveaways WHERE ends_at > ?1", params![now as i64], |row| row.get(0)).expect("Can't count giveaways");
+        count as usize
+    }
+
+    fn insert_tombstone(&mut self, tombstone: Tombstone) {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO tombstones (key, reason, removed_at, ends_at, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                tombstone.key,
+                tombstone.reason,
+                tombstone.removed_at as i64,
+                tombstone.giveaway.ends_at() as i64,
+                serde_json::to_string(&tombstone.giveaway).expect("Can't serialize giveaway"),
+            ],
+        ).expect("Can't write tombstone to database");
+    }
+
+    fn tombstone(&self, key: &str) -> Option<Tombstone> {
+        read_tombstones_where(&self.connection, "key = ?1", &[&key]).pop()
+    }
+
+    fn is_buried(&self, key: &str) -> bool {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM tombstones WHERE key = ?1", params![key], |row| row.get(0)).expect("Can't read tombstones");
+        count > 0
+    }
+
+    fn iter_tombstones(&self) -> Box<dyn Iterator<Item = Tombstone> + '_> {
+        Box::new(read_tombstones_where(&self.connection, "1", NO_PARAMS).into_iter())
+    }
+
+    fn count_removed_early(&self) -> usize {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM tombstones WHERE removed_at < ends_at", NO_PARAMS, |row| row.get(0)).expect("Can't count tombstones");
+        count as usize
+    }
+
+    fn flush(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::check_store;
+
+    #[test]
+    fn sqlite_store() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        check_store(&mut SqliteStore { connection });
+    }
+}
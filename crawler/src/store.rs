@@ -0,0 +1,265 @@
+use crate::{config::{Config, DatabaseBackend}, database::{Error, JsonStore}, sqlite::SqliteStore};
+use format::giveaway::SearchResult;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+/// What remains of a giveaway which disappeared from gleam.io.
+/// Buried giveaways are never loaded again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub key: String,
+    /// Why the giveaway was removed
+    pub reason: String,
+    pub removed_at: u64,
+    /// The last known state of the giveaway
+    pub giveaway: SearchResult,
+}
+
+impl Tombstone {
+    /// Whether the giveaway disappeared before the end of the campaign.
+    pub fn removed_early(&self) -> bool {
+        self.removed_at < self.giveaway.ends_at()
+    }
+}
+
+/// A storage backend for giveaways.
+pub trait GiveawayStore {
+    fn get(&self, key: &str) -> Option<SearchResult>;
+
+    fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert a giveaway, merging it with the stored one if there is one.
+    fn upsert(&mut self, giveaway: SearchResult);
+
+    /// Remove a giveaway. Returns false if it was not stored.
+    fn delete(&mut self, key: &str) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn iter_all(&self) -> Box<dyn Iterator<Item = SearchResult> + '_>;
+
+    /// Iterate on giveaways which are not over at the time `now`.
+    fn iter_running(&self, now: u64) -> Box<dyn Iterator<Item = SearchResult> + '_>;
+
+    fn count_running(&self, now: u64) -> usize {
+        self.iter_running(now).count()
+    }
+
+    /// Insert a tombstone, replacing the one with the same key if there is one.
+    fn insert_tombstone(&mut self, tombstone: Tombstone);
+
+    fn tombstone(&self, key: &str) -> Option<Tombstone>;
+
+    fn is_buried(&self, key: &str) -> bool {
+        self.tombstone(key).is_some()
+    }
+
+    fn iter_tombstones(&self) -> Box<dyn Iterator<Item = Tombstone> + '_>;
+
+    /// Count the giveaways which disappeared before the end of their campaign.
+    fn count_removed_early(&self) -> usize {
+        self.iter_tombstones().filter(|t| t.removed_early()).count()
+    }
+
+    /// Remove a giveaway, keeping a tombstone instead. Returns false if it was not stored.
+    fn bury(&mut self, key: &str, reason: &str, now: u64) -> bool {
+        let giveaway = match self.get(key) {
+            Some(giveaway) => giveaway,
+            None => return false,
+        };
+        self.delete(key);
+        self.insert_tombstone(Tombstone {
+            key: key.to_string(),
+            reason: reason.to_string(),
+            removed_at: now,
+            giveaway,
+        });
+        true
+    }
+
+    /// Make sure all changes are persisted.
+    fn flush(&mut self);
+}
+
+/// A store keeping everything in memory.
+/// Nothing is persisted, which makes it useful for testing.
+#[derive(Default)]
+pub struct MemoryStore {
+    giveaways: HashMap<String, SearchResult>,
+    tombstones: HashMap<String, Tombstone>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl GiveawayStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<SearchResult> {
+        self.giveaways.get(key).cloned()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.giveaways.contains_key(key)
+    }
+
+    fn upsert(&mut self, giveaway: SearchResult) {
+        let giveaway = match self.giveaways.remove(&giveaway.giveaway.campaign.key) {
+            Some(old_giveaway) => old_giveaway + giveaway,
+            None => giveaway,
+        };
+        self.giveaways.insert(giveaway.giveaway.campaign.key.clone(), giveaway);
+    }
+
+    fn delete(&mut self, key: &str) -> bool {
+        self.giveaways.remove(key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.giveaways.len()
+    }
+
+    fn iter_all(&self) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        Box::new(self.giveaways.values().cloned())
+    }
+
+    fn iter_running(&self, now: u64) -> Box<dyn Iterator<Item = SearchResult> + '_> {
+        Box::new(self.giveaways.values().filter(move |g| g.ends_at() > now).cloned())
+    }
+
+    fn insert_tombstone(&mut self, tombstone: Tombstone) {
+        self.tombstones.insert(tombstone.key.clone(), tombstone);
+    }
+
+    fn tombstone(&self, key: &str) -> Option<Tombstone> {
+        self.tombstones.get(key).cloned()
+    }
+
+    fn is_buried(&self, key: &str) -> bool {
+        self.tombstones.contains_key(key)
+    }
+
+    fn iter_tombstones(&self) -> Box<dyn Iterator<Item = Tombstone> + '_> {
+        Box::new(self.tombstones.values().cloned())
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// Open the store selected in the configuration file.
+/// Exits if the database is unreadable and can't be recovered.
+pub fn open_store(config: &Config) -> Box<dyn GiveawayStore> {
+    let store: Result<Box<dyn GiveawayStore>, Error> = match config.database_backend {
+        DatabaseBackend::Json => JsonStore::open(&config.database_file, config.backups.as_ref()).map(|store| Box::new(store) as Box<dyn GiveawayStore>),
+        DatabaseBackend::Sqlite => SqliteStore::open(&config.database_file, config.backups.as_ref()).map(|store| Box::new(store) as Box<dyn GiveawayStore>),
+    };
+    match store {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Can't open the database {}: {}", config.database_file, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use format::{giveaway::IncompleteGiveaway, campaign::IncompleteCampaign, incentive::IncompleteIncentive, taxonomy::Taxonomy, eligibility::Eligibility};
+
+    pub(crate) fn giveaway(key: &str, last_updated: u64, ends_at: u64) -> SearchResult {
+        let mut entry_evolution = HashMap::new();
+        entry_evolution.insert(last_updated.to_string(), 42);
+        SearchResult {
+            giveaway: IncompleteGiveaway {
+                campaign: IncompleteCampaign {
+                    starts_at: 0,
+                    ends_at,
+                    key: String::from(key),
+                    name: String::from("campaign name"),
+                    language: String::from("en"),
+                    site_name: String::from("site"),
+                    site_url: String::from("site.com"),
+                    finished: false,
+                    paused: false,
+                    login_types: Vec::new(),
+                    stand_alone_url: format!("https://gleam.io/{}/campaign-name", key),
+                    campaign_type: String::from("Competition"),
+                    terms_and_conditions: String::new(),
+                    announce_winners: true,
+                    winners: None,
+                },
+                entry_methods: Vec::new(),
+                incentive: IncompleteIncentive {
+                    name: String::from("prize"),
+                    description: String::from("a prize"),
+                    incentive_type: String::from("Prize"),
+                },
+                taxonomy: Taxonomy::default(),
+                eligibility: Eligibility::default(),
+            },
+            last_updated,
+            failed_at: None,
+            failures: 0,
+            referers: vec![format!("https://example.com/{}", last_updated)],
+            entry_count: Some(42),
+            entry_evolution: Some(entry_evolution),
+        }
+    }
+
+    /// Check the behavior every store must have.
+    pub(crate) fn check_store(store: &mut dyn GiveawayStore) {
+        store.upsert(giveaway("abcde", 30, 1000));
+        store.upsert(giveaway("fghij", 10, 100));
+        store.upsert(giveaway("klmno", 20, 1000));
+        store.upsert(giveaway("abcde", 5, 1000));
+        store.flush();
+
+        assert_eq!(store.len(), 3);
+        assert!(store.contains("fghij"));
+        assert!(!store.contains("zzzzz"));
+
+        // Merging keeps the most recent data and every referer
+        let abcde = store.get("abcde").unwrap();
+        assert_eq!(abcde.last_updated, 30);
+        let mut referers = abcde.referers.clone();
+        referers.sort();
+        assert_eq!(referers, vec!["https://example.com/30", "https://example.com/5"]);
+        assert_eq!(abcde.entry_evolution.unwrap().len(), 2);
+
+        let mut running = store.iter_running(500).map(|g| g.giveaway.campaign.key).collect::<Vec<String>>();
+        running.sort();
+        assert_eq!(running, vec!["abcde", "klmno"]);
+        assert_eq!(store.count_running(500), 2);
+
+        assert!(store.delete("abcde"));
+        assert!(!store.delete("abcde"));
+        store.flush();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.iter_all().count(), 2);
+
+        // Buried giveaways are replaced by a tombstone keeping their data
+        assert!(store.bury("fghij", "campaign not found", 50));
+        assert!(!store.bury("fghij", "campaign not found", 60));
+        store.flush();
+        assert_eq!(store.len(), 1);
+        assert!(!store.contains("fghij"));
+        assert!(store.is_buried("fghij"));
+        assert!(!store.is_buried("klmno"));
+        let tombstone = store.tombstone("fghij").unwrap();
+        assert_eq!(tombstone.reason, "campaign not found");
+        assert_eq!(tombstone.removed_at, 50);
+        assert_eq!(tombstone.giveaway.last_updated, 10);
+        assert!(tombstone.removed_early());
+        assert_eq!(store.iter_tombstones().count(), 1);
+        assert_eq!(store.count_removed_early(), 1);
+    }
+
+    #[test]
+    fn memory_store() {
+        check_store(&mut MemoryStore::new());
+    }
+}
@@ -1,4 +1,3 @@
-use format::classifier::classify;
 use crate::{
     format::*,
     messages::{Message, Message::*},
@@ -9,11 +8,11 @@ use crate::{
 };
 use format::{prelude::*, contestant::MaybeUninitContestant};
 use serde_json::{from_str, json, Value};
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, rc::Rc, time::Duration};
 use string_tools::*;
 use web_sys::window;
 use yew::prelude::*;
-use format::classifier::{EntryType, RequestType};
+use format::typed_entry::{TypedEntry, RequestType};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
@@ -78,7 +77,7 @@ pub async fn run(
     #[cfg(debug_assertions)]
     log!("Contestant: {:#?}", init_contestant);
     let mut contestant: Contestant = match init_contestant.contestant {
-        MaybeUninitContestant::Connected(contestant) => contestant,
+        MaybeUninitContestant::Connected(contestant) => *contestant,
         MaybeUninitContestant::Disconnected{..} => {
             return Err(Warning("You have to login to gleam.io to use the bot.".to_string()));
         }
@@ -228,23 +227,23 @@ pub async fn run(
         }
 
         // Analyse the entry
-        let entry_type = match classify(entry) {
-            Some(entry_type) => match entry_type {
-                EntryType::TwitchFollow => {
+        let typed_entry = match TypedEntry::try_from(entry) {
+            Ok(typed_entry) => match typed_entry {
+                TypedEntry::TwitchFollow { .. } => {
                     if !settings.borrow().auto_follow_twitch {
                         warn("Ignored an entry since automatic twitch follow is disabled by your settings.".to_string());
                         next();
                         continue;
                     }
-                    entry_type
+                    typed_entry
                 }
-                entry_type => entry_type,
+                typed_entry => typed_entry,
             },
-            None => {
-                log!("Unknown entry method: {}\nworkflow: {:?}\ntemplate: {:?}\nmethod_type: {:?}\nconfigs: [\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?}\n]", entry.entry_type, entry.workflow, entry.template, entry.method_type, entry.config1, entry.config2, entry.config3, entry.config4, entry.config5, entry.config6, entry.config7, entry.config8, entry.config9);
+            Err(e) => {
+                log!("Unknown entry method ({}): {}\nworkflow: {:?}\ntemplate: {:?}\nmethod_type: {:?}\nconfigs: [\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?}\n]", e, entry.entry_type, entry.workflow, entry.template, entry.method_type, entry.config1, entry.config2, entry.config3, entry.config4, entry.config5, entry.config6, entry.config7, entry.config8, entry.config9);
                 if settings.borrow().display_dev_messages {
                     notify(Warning(format!(
-                        "Unknown entry method: {}\nworkflow: {:?}\ntemplate: {:?}\nmethod_type: {:?}\nconfigs: [\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?}\n]", entry.entry_type, entry.workflow, entry.template, entry.method_type, entry.config1, entry.config2, entry.config3, entry.config4, entry.config5, entry.config6, entry.config7, entry.config8, entry.config9
+                        "Unknown entry method ({}): {}\nworkflow: {:?}\ntemplate: {:?}\nmethod_type: {:?}\nconfigs: [\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?},\n\t{:?}\n]", e, entry.entry_type, entry.workflow, entry.template, entry.method_type, entry.config1, entry.config2, entry.config3, entry.config4, entry.config5, entry.config6, entry.config7, entry.config8, entry.config9
                     )));
                 }
                 next();
@@ -252,33 +251,10 @@ pub async fn run(
             }
         };
 
-        let get_config = |index: u8| -> Option<&String> {
-            match index {
-                1 => entry.config1.as_ref(),
-                2 => entry.config2.as_ref(),
-                3 => entry.config3.as_ref(),
-                4 => entry.config4.as_ref(),
-                5 => entry.config5.as_ref(),
-                6 => entry.config6.as_ref(),
-                7 => entry.config7.as_ref(),
-                8 => entry.config8.as_ref(),
-                9 => entry.config9.as_ref(),
-                _ => panic!("Invalid entry index"),
-            }
-        };
-
         // Generate a the root of a validation request
-        let details: (Value, bool, bool) = match entry_type.get_request_type() {
-            RequestType::TwitterFollow => {
+        let details: (Value, bool, bool) = match typed_entry.get_request_type() {
+            RequestType::TwitterFollow { username } => {
                 if settings.borrow().auto_follow_twitter {
-                    let username = match &entry.config1 {
-                        Some(username) => username,
-                        None => {
-                            err_next("Invalid twitter entry method 00".to_string());
-                            continue;
-                        }
-                    };
-    
                     let url = format!(
                         "https://twitter.com/intent/follow?screen_name={}&gleambot=true",
                         username
@@ -300,17 +276,9 @@ pub async fn run(
                     continue;
                 }
             }
-            RequestType::TwitterRetweet => {
+            RequestType::TwitterRetweet { tweet_url } => {
                 if settings.borrow().auto_retweet {
-                    let url = match &entry.config1 {
-                        Some(url) => url,
-                        None => {
-                            err_next("Invalid twitter entry method 01".to_string());
-                            continue;
-                        }
-                    };
-    
-                    let id = match get_all_after_strict(&url, "/status/") {
+                    let id = match get_all_after_strict(tweet_url, "/status/") {
                         Some(id) => id,
                         None => {
                             err_next("Invalid twitter entry method 02".to_string());
@@ -338,17 +306,9 @@ pub async fn run(
                     continue;
                 }
             }
-            RequestType::TwitterTweet(data_included) => {
-                if data_included && settings.borrow().auto_tweet {
-                    // Get the text
-                    let text = match &entry.config1 {
-                        Some(text) => text,
-                        None => {
-                            err_next("Invalid twitter entry method 03".to_string());
-                            continue;
-                        }
-                    };
-                    
+            RequestType::TwitterTweet(text) => {
+                let auto_tweet = settings.borrow().auto_tweet;
+                if let (Some(text), true) = (text, auto_tweet) {
                     // Build the URL
                     let url = format!(
                         "https://twitter.com/intent/tweet?text={}%20{}&gleambot=true",
@@ -367,7 +327,7 @@ pub async fn run(
                     
                     sleep(Duration::from_secs(15)).await;
                     (twitter_value.clone(), true, true)
-                } else if !data_included && settings.borrow().auto_tweet_share {
+                } else if text.is_none() && settings.borrow().auto_tweet_share {
                     use format::shortener::Shortener;
 
                     // Get the shortener
@@ -444,8 +404,8 @@ pub async fn run(
                     continue;
                 }
             }
-            RequestType::Answer(separator, i) => {   
-                if let Some(answer) = get_config(i).map(|a| a.split(separator).collect::<Vec<&str>>().get(0).map(|a| a.trim().to_string())).flatten() {
+            RequestType::Answer(answers) => {
+                if let Some(answer) = answers.first() {
                     (
                         Value::String(answer.replace("&#39;", "'")),
                         true,
@@ -467,14 +427,13 @@ pub async fn run(
                 )
             }
             RequestType::Simple(details, prop_dbg, prop_efd) => {
-                if entry_type == EntryType::EmailSubscribe && !settings.borrow().auto_email_subscribe {
+                if matches!(typed_entry, TypedEntry::EmailSubscribe { .. }) && !settings.borrow().auto_email_subscribe {
                     next();
                     continue;
                 }
                 (details, prop_dbg, prop_efd)
             }
             RequestType::SimpleWithDelay(details, delay) => {
-                let delay = get_config(delay).unwrap().parse().unwrap(); // unwrap because values have been checked by the classifier
                 sleep(Duration::from_secs(delay)).await;
                 details
             }
@@ -1,8 +1,7 @@
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
-use crate::{shortener::Shortener, contestant::Winner};
+use serde_json::{Map, Value};
+use crate::{shortener::Shortener, contestant::Winner, strictness::*};
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Campaign {
     pub starts_at: u64,
@@ -64,10 +63,20 @@ pub struct Campaign {
     pub cookie_check_disabled: Option<Value>,
     pub winners: Option<Vec<Winner>>,
     pub shortener: Shortener,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+impl CollectUnknownFields for Campaign {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("Campaign", &self.extra);
+        for winner in self.winners.iter().flatten() {
+            winner.collect_unknown_fields(report);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IncompleteCampaign {
     pub starts_at: u64,
     pub ends_at: u64,
@@ -82,7 +91,10 @@ pub struct IncompleteCampaign {
     pub stand_alone_url: String,
     pub campaign_type: String,
     pub terms_and_conditions: String,
-    pub announce_winners: bool
+    pub announce_winners: bool,
+    /// Only known once the campaign is over
+    #[serde(default)]
+    pub winners: Option<Vec<Winner>>,
 }
 
 impl From<Campaign> for IncompleteCampaign {
@@ -102,6 +114,7 @@ impl From<Campaign> for IncompleteCampaign {
             campaign_type: campaign.campaign_type,
             terms_and_conditions: campaign.terms_and_conditions,
             announce_winners: campaign.announce_winners,
+            winners: campaign.winners,
         }
     }
 }
\ No newline at end of file
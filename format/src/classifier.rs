@@ -1,22 +1,87 @@
 use crate::entry::EntryMethod;
-use list::*;
+use regex::Regex;
+use serde::{Serialize, Deserialize, Serializer, Deserializer, de::Error};
+use serde_json::Value;
+use std::sync::OnceLock;
+use url::Url;
 
-#[allow(dead_code)]
-pub enum Arg<'a> {
+/// The rules used when no other rules are set, in the TOML format.
+pub const BUILTIN_RULES: &str = include_str!("../rules/builtin.toml");
+
+/// The number of fields checked by a verifier, without its JSON checks.
+pub const FIELD_COUNT: usize = 13;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum Arg {
     IsNumber,
-    IsExact(Option<&'a String>),
-    IsIn(&'a [&'static str]),
-    Is(&'static str),
+    IsExact(Option<String>),
+    IsIn(Vec<String>),
+    Is(String),
     Exists,
     Lacks,
     IsEmpty,
     IsNotEmpty,
+    #[default]
     Anything,
+    /// An absolute URL with a host, whatever its scheme
     IsUrl,
-    Or(&'a Arg<'a>, &'a Arg<'a>),
+    /// An http or https URL
+    IsHttpUrl,
+    /// An http or https URL on one of these hosts or their subdomains
+    IsUrlOf(Vec<String>),
+    /// Contains a match of this regular expression (use `^` and `$` to match the whole value)
+    Matches(Pattern),
+    /// An integer greater than or equal to this one
+    AtLeast(i64),
+    /// An integer lower than or equal to this one
+    AtMost(i64),
+    /// Matches if any of the operators matches
+    Or(Vec<Arg>),
+    /// Matches if all the operators match
+    And(Vec<Arg>),
+    Not(Box<Arg>),
+}
+
+/// A regular expression, compiled when the rules are loaded.
+#[derive(Debug, Clone)]
+pub struct Pattern(pub Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(Pattern).map_err(D::Error::custom)
+    }
 }
 
-impl<'a> Arg<'a> {
+fn parse_url(value: &str) -> Option<Url> {
+    let url = Url::parse(value).ok()?;
+    if (url.scheme() == "http" || url.scheme() == "https") && url.host_str().is_some() {
+        Some(url)
+    } else {
+        None
+    }
+}
+
+fn parse_integer(value: Option<&String>) -> Result<i64, &'static str> {
+    match value {
+        Some(value) => value.parse::<i64>().map_err(|_| "Expected Number, found String"),
+        None => Err("Expected Number, found Null"),
+    }
+}
+
+impl Arg {
     pub fn matches(&self, value: Option<&String>) -> Result<(), &'static str> {
         match self {
             Arg::IsNumber => {
@@ -31,7 +96,7 @@ impl<'a> Arg<'a> {
                 }
             }
             Arg::Is(expected_value) => {
-                if value.map(|s| s.as_str()) == Some(expected_value) {
+                if value.map(|s| s.as_str()) == Some(expected_value.as_str()) {
                     Ok(())
                 } else {
                     Err("Expected a specific value, got something else")
@@ -39,7 +104,7 @@ impl<'a> Arg<'a> {
             }
             Arg::IsIn(values) => {
                 for expected_value in values.iter() {
-                    if Some(*expected_value) == value.map(|s| s.as_str()) {
+                    if Some(expected_value.as_str()) == value.map(|s| s.as_str()) {
                         return Ok(());
                     }
                 }
@@ -66,7 +131,7 @@ impl<'a> Arg<'a> {
                     Err("Expected an empty value, got something else")
                 }
             }
-            Arg::IsNotEmpty | Arg::IsUrl => {
+            Arg::IsNotEmpty => {
                 if let Some(value) = value {
                     if !value.is_empty() {
                         Ok(())
@@ -78,747 +143,368 @@ impl<'a> Arg<'a> {
                 }
             }
             Arg::IsExact(expected_value) => {
-                if &value == expected_value {
+                if value == expected_value.as_ref() {
                     Ok(())
                 } else {
                     Err("Expected a specific value, got something else")
                 }
             }
+            Arg::IsUrl => {
+                if let Some(value) = value {
+                    if Url::parse(value).map(|url| url.has_host()).unwrap_or(false) {
+                        Ok(())
+                    } else {
+                        Err("Expected URL")
+                    }
+                } else {
+                    Err("Expected URL, found Null")
+                }
+            }
+            Arg::IsHttpUrl => {
+                if let Some(value) = value {
+                    if parse_url(value).is_some() {
+                        Ok(())
+                    } else {
+                        Err("Expected URL")
+                    }
+                } else {
+                    Err("Expected URL, found Null")
+                }
+            }
+            Arg::IsUrlOf(hosts) => {
+                let url = match value {
+                    Some(value) => parse_url(value).ok_or("Expected URL")?,
+                    None => return Err("Expected URL, found Null"),
+                };
+                let host = url.host_str().unwrap_or_default();
+                let allowed = hosts.iter().any(|allowed| {
+                    let allowed = allowed.to_lowercase();
+                    host == allowed || host.strip_suffix(allowed.as_str()).map(|sub| sub.ends_with('.')).unwrap_or(false)
+                });
+                if allowed {
+                    Ok(())
+                } else {
+                    Err("Unexpected host")
+                }
+            }
+            Arg::Matches(pattern) => {
+                if let Some(value) = value {
+                    if pattern.0.is_match(value) {
+                        Ok(())
+                    } else {
+                        Err("Does not match the pattern")
+                    }
+                } else {
+                    Err("Expected String, found Null")
+                }
+            }
+            Arg::AtLeast(min) => {
+                if parse_integer(value)? >= *min {
+                    Ok(())
+                } else {
+                    Err("Number too small")
+                }
+            }
+            Arg::AtMost(max) => {
+                if parse_integer(value)? <= *max {
+                    Ok(())
+                } else {
+                    Err("Number too large")
+                }
+            }
             Arg::Anything => Ok(()),
-            Arg::Or(c1, c2) => {
-                if c2.matches(value).is_ok() {
+            Arg::Or(args) => {
+                if args.iter().any(|arg| arg.matches(value).is_ok()) {
+                    Ok(())
+                } else {
+                    args.first().map(|arg| arg.matches(value)).unwrap_or(Err("Expected at least one operator"))
+                }
+            }
+            Arg::And(args) => args.iter().try_for_each(|arg| arg.matches(value)),
+            Arg::Not(arg) => {
+                if arg.matches(value).is_err() {
                     Ok(())
                 } else {
-                    c1.matches(value)
+                    Err("Unexpected value")
                 }
             }
         }
     }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Arg::IsIn(values) if values.is_empty() => Err(String::from("IsIn needs at least one value")),
+            Arg::IsUrlOf(hosts) if hosts.is_empty() => Err(String::from("IsUrlOf needs at least one host")),
+            Arg::Or(args) if args.len() < 2 => Err(String::from("Or needs at least two operators")),
+            Arg::And(args) if args.len() < 2 => Err(String::from("And needs at least two operators")),
+            Arg::Or(args) | Arg::And(args) => args.iter().try_for_each(Arg::validate),
+            Arg::Not(arg) => arg.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The fields of an entry method which are JSON values instead of strings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonField {
+    Config,
+    ConfigSelections,
 }
 
-pub struct Verifyer<'a> {
-    entry_type: &'a str,
-    workflow: Arg<'a>,
-    template: Arg<'a>,
-    method_type: Arg<'a>,
-    configs: [Arg<'a>; 9],
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JsonKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
 }
 
-impl<'a> Verifyer<'a> {
-    pub const fn new(
-        entry_type: &'a str,
-        workflow: Arg<'a>,
-        template: Arg<'a>,
-        method_type: Arg<'a>,
-        configs: [Arg<'a>; 9],
-    ) -> Verifyer<'a> {
-        Verifyer {
-            entry_type,
-            workflow,
-            template,
-            method_type,
-            configs,
+/// A check on a value inside a JSON field of an entry method.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonCheck {
+    pub field: JsonField,
+    /// A JSON pointer like `/0/label`, or nothing for the whole field
+    #[serde(default)]
+    pub pointer: String,
+    /// The type of the value, if it matters
+    pub kind: Option<JsonKind>,
+    /// Compared with the value: strings as they are, null as a missing value and other values as JSON
+    #[serde(default)]
+    pub arg: Arg,
+}
+
+impl JsonCheck {
+    fn name(&self) -> &'static str {
+        match self.field {
+            JsonField::Config => "config",
+            JsonField::ConfigSelections => "config_selections",
         }
     }
 
+    pub fn matches(&self, entry: &EntryMethod) -> Result<(), &'static str> {
+        let field = match self.field {
+            JsonField::Config => &entry.config,
+            JsonField::ConfigSelections => return self.check(&Value::Array(entry.config_selections.clone())),
+        };
+        self.check(field)
+    }
+
+    fn check(&self, field: &Value) -> Result<(), &'static str> {
+        let value = field.pointer(&self.pointer);
+        if let Some(kind) = self.kind {
+            let found = match value {
+                None | Some(Value::Null) => JsonKind::Null,
+                Some(Value::Bool(_)) => JsonKind::Bool,
+                Some(Value::Number(_)) => JsonKind::Number,
+                Some(Value::String(_)) => JsonKind::String,
+                Some(Value::Array(_)) => JsonKind::Array,
+                Some(Value::Object(_)) => JsonKind::Object,
+            };
+            if found != kind {
+                return Err("Unexpected JSON type");
+            }
+        }
+        let text = match value {
+            None | Some(Value::Null) => None,
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(value) => Some(value.to_string()),
+        };
+        self.arg.matches(text.as_ref())
+    }
+}
+
+/// A rule recognizing a kind of entry method.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verifyer {
+    /// The entry type of the entry methods matching this rule
+    pub name: EntryType,
+    pub entry_type: String,
+    pub workflow: Arg,
+    pub template: Arg,
+    pub method_type: Arg,
+    /// Compared with `config1` to `config9`
+    pub configs: [Arg; 9],
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub json: Vec<JsonCheck>,
+}
+
+impl Verifyer {
     pub fn matches(&self, entry: &EntryMethod) -> Result<(), (&'static str, &'static str)> {
+        match self.mismatches(entry).into_iter().next() {
+            Some(mismatch) => Err(mismatch),
+            None => Ok(()),
+        }
+    }
+
+    /// Check all the fields of an entry method, instead of stopping at the first mismatch.
+    pub fn mismatches(&self, entry: &EntryMethod) -> Vec<(&'static str, &'static str)> {
+        const CONFIGS: [&str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+        let mut mismatches = Vec::new();
         if self.entry_type != entry.entry_type {
-            return Err(("entry_type", "does not match"))
+            mismatches.push(("entry_type", "does not match"));
+        }
+        let fields = [
+            ("workflow", &self.workflow, entry.workflow.as_ref()),
+            ("template", &self.template, Some(&entry.template)),
+            ("method_type", &self.method_type, entry.method_type.as_ref()),
+        ];
+        for (name, arg, value) in fields.iter() {
+            if let Err(e) = arg.matches(*value) {
+                mismatches.push((name, e));
+            }
+        }
+        for (index, arg) in self.configs.iter().enumerate() {
+            if let Err(e) = arg.matches(entry.config(index + 1)) {
+                mismatches.push((CONFIGS[index], e));
+            }
+        }
+        for check in &self.json {
+            if let Err(e) = check.matches(entry) {
+                mismatches.push((check.name(), e));
+            }
+        }
+        mismatches
+    }
+
+    /// The number of fields checked by this verifier.
+    pub fn field_count(&self) -> usize {
+        FIELD_COUNT + self.json.len()
+    }
+
+    /// Whether the two verifiers check the same things, whatever their names.
+    fn same_checks(&self, other: &Verifyer) -> bool {
+        self.entry_type == other.entry_type
+            && self.workflow == other.workflow
+            && self.template == other.template
+            && self.method_type == other.method_type
+            && self.configs == other.configs
+            && self.json == other.json
+    }
+}
+
+/// A set of verifiers, by priority: an entry method gets the type of the first verifier it matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub rules: Vec<Verifyer>,
+}
+
+#[derive(Debug)]
+pub enum RuleError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// The index of the rule and the problem
+    Invalid(usize, String),
+}
+
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::Io(e) => write!(f, "can't read the rules: {}", e),
+            RuleError::Toml(e) => write!(f, "invalid TOML rules: {}", e),
+            RuleError::Json(e) => write!(f, "invalid JSON rules: {}", e),
+            RuleError::Invalid(index, reason) => write!(f, "invalid rule #{}: {}", index + 1, reason),
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn builtin() -> RuleSet {
+        RuleSet::from_toml(BUILTIN_RULES).expect("The built-in rules are invalid")
+    }
+
+    pub fn from_toml(data: &str) -> Result<RuleSet, RuleError> {
+        let rules: RuleSet = toml::from_str(data).map_err(RuleError::Toml)?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    pub fn from_json(data: &str) -> Result<RuleSet, RuleError> {
+        let rules: RuleSet = serde_json::from_str(data).map_err(RuleError::Json)?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Read a rules file, in JSON if its extension is `.json` and in TOML otherwise.
+    pub fn load(path: &str) -> Result<RuleSet, RuleError> {
+        let data = std::fs::read_to_string(path).map_err(RuleError::Io)?;
+        if path.ends_with(".json") {
+            RuleSet::from_json(&data)
+        } else {
+            RuleSet::from_toml(&data)
+        }
+    }
+
+    fn validate(&self) -> Result<(), RuleError> {
+        if self.rules.is_empty() {
+            return Err(RuleError::Invalid(0, String::from("there are no rules")));
+        }
+        for (index, rule) in self.rules.iter().enumerate() {
+            let invalid = |reason: String| RuleError::Invalid(index, format!("{} ({})", reason, rule.name));
+            if rule.name.to_string().is_empty() {
+                return Err(invalid(String::from("the name is empty")));
+            }
+            if rule.entry_type.is_empty() {
+                return Err(invalid(String::from("the entry type is empty")));
+            }
+            let json_args = rule.json.iter().map(|check| &check.arg);
+            for arg in [&rule.workflow, &rule.template, &rule.method_type].iter().copied().chain(rule.configs.iter()).chain(json_args) {
+                arg.validate().map_err(invalid)?;
+            }
+            if let Some(previous) = self.rules[..index].iter().position(|previous| previous.same_checks(rule)) {
+                return Err(invalid(format!("it can never match, rule #{} checks the same things", previous + 1)));
+            }
         }
-        self.workflow
-            .matches(entry.workflow.as_ref())
-            .map_err(|e| ("workflow", e))?;
-        self.template
-            .matches(Some(&entry.template))
-            .map_err(|e| ("template", e))?;
-        self.method_type
-            .matches(entry.method_type.as_ref())
-            .map_err(|e| ("method_type", e))?;
-        self.configs[0]
-            .matches(entry.config1.as_ref())
-            .map_err(|e| ("1", e))?;
-        self.configs[1]
-            .matches(entry.config2.as_ref())
-            .map_err(|e| ("2", e))?;
-        self.configs[2]
-            .matches(entry.config3.as_ref())
-            .map_err(|e| ("3", e))?;
-        self.configs[3]
-            .matches(entry.config4.as_ref())
-            .map_err(|e| ("4", e))?;
-        self.configs[4]
-            .matches(entry.config5.as_ref())
-            .map_err(|e| ("5", e))?;
-        self.configs[5]
-            .matches(entry.config6.as_ref())
-            .map_err(|e| ("6", e))?;
-        self.configs[6]
-            .matches(entry.config7.as_ref())
-            .map_err(|e| ("7", e))?;
-        self.configs[7]
-            .matches(entry.config8.as_ref())
-            .map_err(|e| ("8", e))?;
-        self.configs[8]
-            .matches(entry.config9.as_ref())
-            .map_err(|e| ("9", e))?;
         Ok(())
     }
+
+    pub fn classify(&self, entry: &EntryMethod) -> Option<EntryType> {
+        self.rules.iter().find(|verifyer| verifyer.matches(entry).is_ok()).map(|verifyer| verifyer.name.clone())
+    }
+
+    /// All the entry types an entry method matches, by priority.
+    /// `classify` only returns the first one, so there should not be more than one.
+    pub fn matching(&self, entry: &EntryMethod) -> Vec<EntryType> {
+        self.rules.iter().filter(|verifyer| verifyer.matches(entry).is_ok()).map(|verifyer| verifyer.name.clone()).collect()
+    }
+
+    /// Compare an entry method with all the verifiers, the closest first.
+    /// If the entry method is classified, the first explanation is its entry type, without mismatches.
+    pub fn classify_explained(&self, entry: &EntryMethod) -> Vec<Explanation> {
+        let mut explanations = self.rules.iter().map(|verifyer| {
+            let mismatches = verifyer.mismatches(entry);
+            Explanation {
+                entry_type: verifyer.name.clone(),
+                matched: verifyer.field_count() - mismatches.len(),
+                fields: verifyer.field_count(),
+                mismatches,
+            }
+        }).collect::<Vec<Explanation>>();
+        explanations.sort_by_key(|explanation| explanation.mismatches.len());
+        explanations
+    }
 }
 
-mod list {
-    use super::Arg::*;
-    use super::*;
+static RULES: OnceLock<RuleSet> = OnceLock::new();
+
+/// Replace the built-in rules.
+/// It must be called before the first classification, or the rules are given back.
+/// Only the crawler does it: the extension and the website always use the built-in rules.
+pub fn set_rules(rules: RuleSet) -> Result<(), RuleSet> {
+    RULES.set(rules)
+}
 
-    pub const INSTAGRAM_VISIT_PROFILE: Verifyer = Verifyer::new(
-        "instagram_visit_profile",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            Or(&Lacks, &IsNumber),
-            IsNotEmpty,
-            Or(&Lacks, &IsNotEmpty),
-            IsIn(&["Complete", "Delay"]),
-            IsNumber,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-
-    pub const INSTAGRAM_VISIT_PROFILE_WITH_QUESTION: Verifyer = Verifyer::new(
-        "instagram_visit_profile",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            Or(&Lacks, &IsNumber),
-            IsNotEmpty,
-            Or(&Lacks, &IsNotEmpty),
-            Is("Question"),
-            IsNumber,
-            IsNotEmpty,
-            IsNotEmpty,
-            IsEmpty,
-        ],
-    );
-    
-    pub const INSTAGRAM_VIEW_POST: Verifyer = Verifyer::new(
-        "instagram_view_post",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            Lacks,
-            Lacks,
-            Or(&Lacks, &IsNumber),
-            Or(&Lacks, &IsNumber),
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const INSTAGRAM_ENTER: Verifyer = Verifyer::new(
-        "instagram_enter",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_QUESTION: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        Is("question"),
-        Is("Ask a question"),
-        [
-            IsNotEmpty,
-            Lacks,
-            Or(&Lacks, &IsNotEmpty),
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Is("50"),
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_ASK_QUESTION: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        IsEmpty,
-        Is("Ask a question"),
-        [
-            IsNotEmpty,
-            Lacks,
-            IsNotEmpty,
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Is("0"),
-            Lacks,
-            Lacks,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_VISIT_QUESTION: Verifyer = Verifyer::new(
-        "custom_action",
-        Is("VisitQuestion"),
-        Is("visit"),
-        Is("Allow question or tracking"),
-        [
-            IsNotEmpty,
-            IsNotEmpty,
-            IsNotEmpty,
-            IsNotEmpty,
-            Or(&IsNotEmpty, &Lacks),
-            Is("simple"),
-            Lacks,
-            Or(&IsNotEmpty, &Lacks),
-            Lacks,
-        ],
-    );
-
-    pub const CUSTOM_ACTION_CHOOSE_OPTION: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        Is("choose_option"),
-        Is("Use tracking"),
-        [
-            IsNotEmpty,
-            Is("unique"),
-            IsNotEmpty,
-            Lacks,
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_BLOG_COMMENT: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        Is("blog_comment"),
-        Is("Allow question or tracking"),
-        [
-            IsNotEmpty,
-            Is("comment"),
-            Anything,
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_BASIC: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        IsEmpty,
-        Is("None"),
-        [
-            IsNotEmpty,
-            Lacks,
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsNumber,
-            Lacks,
-            Lacks,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_VISIT_AUTO: Verifyer = Verifyer::new(
-        "custom_action",
-        Or(&IsEmpty, &Is("VisitAuto")),
-        Is("visit"),
-        Is("Use tracking"),
-        [
-            IsNotEmpty,
-            IsNotEmpty,
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Is("simple"),
-            Lacks,
-            Or(&IsNotEmpty, &Lacks),
-            Lacks,
-        ],
-    );
-
-    pub const CUSTOM_ACTION_VISIT_DELAY: Verifyer = Verifyer::new(
-        "custom_action",
-        Is("VisitDelay"),
-        Is("visit"),
-        Is("Use tracking"),
-        [
-            IsNotEmpty, // raw text to display
-            IsNotEmpty, // seems to be an uid, ex: visit-394265639250
-            IsNotEmpty, // html text to display
-            Lacks,
-            Lacks,
-            Is("simple"),
-            IsNumber, // seconds to wait
-            IsNotEmpty, // json object representing a link (often useless)
-            Lacks,
-        ],
-    );
-    
-    pub const CUSTOM_ACTION_BONUS: Verifyer = Verifyer::new(
-        "custom_action",
-        Lacks,
-        Is("bonus"),
-        Is("None"),
-        [
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-    
-    pub const EMAIL_SUBSCRIBE: Verifyer = Verifyer::new(
-        "email_subscribe",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Or(&IsNotEmpty, &Lacks),
-            Lacks,
-            Is("Off"),
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const FACEBOOK_ENTER: Verifyer = Verifyer::new(
-        "facebook_enter",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            Or(&IsNotEmpty, &IsUrl),
-            IsIn(&["Complete", "Like"]),
-            Or(&Lacks, &IsUrl),
-            Or(&Lacks, &IsNotEmpty),
-            Or(&Lacks, &IsNumber),
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const FACEBOOK_VISIT_COMPLETE: Verifyer = Verifyer::new(
-        "facebook_visit",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            IsNotEmpty,
-            IsNumber,
-            Is("Complete"),
-            Is("Complete"),
-            IsNumber,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const FACEBOOK_VISIT_LIKE: Verifyer = Verifyer::new(
-        "facebook_visit",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            IsNotEmpty,
-            IsNumber,
-            Is("Like"),
-            Is("Complete"),
-            IsNumber,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const FACEBOOK_VISIT_LIKE_WITH_QUESTION: Verifyer = Verifyer::new(
-        "facebook_visit",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            IsNotEmpty,
-            IsNumber,
-            Is("Like"),
-            Is("Question"),
-            IsNumber,
-            IsNotEmpty,
-            IsNotEmpty,
-            IsEmpty,
-        ],
-    );
-    
-    pub const FACEBOOK_VIEW_POST: Verifyer = Verifyer::new(
-        "facebook_view_post",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            IsNotEmpty,
-            IsIn(&["post", "photo", "video"]),
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const PINTEREST_VISIT_COMPLETE: Verifyer = Verifyer::new(
-        "pinterest_visit",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Is("Complete"),
-            Is("Complete"),
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const PINTEREST_VISIT_FOLLOW: Verifyer = Verifyer::new(
-        "pinterest_visit",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Is("Follow"),
-            Is("Complete"),
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const YOUTUBE_VISIT_CHANNEL: Verifyer = Verifyer::new(
-        "youtube_visit_channel",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Anything,
-            Is("Complete"),
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-
-    pub const YOUTUBE_VISIT_CHANNEL_WITH_DELAY: Verifyer = Verifyer::new(
-        "youtube_visit_channel",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl, // url of the channel
-            IsNotEmpty, // name of the channel
-            Is("Delay"),
-            IsNumber, // seconds to wait
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const YOUTUBE_VISIT_CHANNEL_WITH_QUESTION: Verifyer = Verifyer::new(
-        "youtube_visit_channel",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl, // url of the channel
-            IsNotEmpty, // username
-            Is("Question"),
-            IsNumber, // unknown number
-            IsNotEmpty, // the question
-            Or(&IsNotEmpty, &Lacks), // responses, optionnal
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const YOUTUBE_ENTER: Verifyer = Verifyer::new(
-        "youtube_enter",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const TWITCHTV_FOLLOW: Verifyer = Verifyer::new(
-        "twitchtv_follow",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-    
-    pub const TWITCHTV_ENTER: Verifyer = Verifyer::new(
-        "twitchtv_enter",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-
-    pub const TWITTER_ENTER: Verifyer = Verifyer::new(
-        "twitter_enter",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const TWITTER_RETWEET: Verifyer = Verifyer::new(
-        "twitter_retweet",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl,
-            IsNotEmpty,
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const TWITTER_TWEET: Verifyer = Verifyer::new(
-        "twitter_tweet",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const TWITTER_FOLLOW: Verifyer = Verifyer::new(
-        "twitter_follow",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty,
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const DISCORD_JOIN_SERVER: Verifyer = Verifyer::new(
-        "discord_join_server",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty, // text to display
-            IsUrl, // invitation link
-            IsNumber,
-            IsNotEmpty, // server name
-            IsNotEmpty, // unknown hex number
-            IsNotEmpty, // channel name
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const LINKEDIN_FOLLOW: Verifyer = Verifyer::new(
-        "linkedin_follow",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl, // profile url
-            IsNotEmpty, // text to display
-            IsNotEmpty, // user name
-            IsNumber,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            IsEmpty,
-        ],
-    );
-
-    pub const STEAM_JOIN_GROUP: Verifyer = Verifyer::new(
-        "steam_join_group",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsUrl, // group url
-            IsNotEmpty, // group name
-            IsNotEmpty, // group name
-            IsNumber,
-            IsNumber, // 1
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const LOYALTY: Verifyer = Verifyer::new(
-        "loyalty",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty, // text to display
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
-
-    pub const SHARE_ACTION: Verifyer = Verifyer::new(
-        "share_action",
-        Lacks,
-        IsEmpty,
-        Lacks,
-        [
-            IsNotEmpty, // text to display
-            Lacks,
-            IsNotEmpty, // more text to display
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-            Lacks,
-        ],
-    );
+/// The rules in use.
+pub fn rules() -> &'static RuleSet {
+    RULES.get_or_init(RuleSet::builtin)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum EntryType {
     InstagramEnter,
     InstagramViewPost,
@@ -856,109 +542,284 @@ pub enum EntryType {
     SteamJoinGroup,
     Loyalty,
     ShareAction,
+    /// An entry type which is only known by a rules file
+    Other(String),
 }
 
-impl EntryType {
-    pub fn get_request_type(&self) -> RequestType {
-        use EntryType::*;
-        use EntryType::{TwitterRetweet, TwitterTweet, TwitterFollow};
-        use RequestType::*;
-        use serde_json::Value::*;
+const BUILTIN_ENTRY_TYPES: [EntryType; 36] = [
+    EntryType::InstagramEnter,
+    EntryType::InstagramViewPost,
+    EntryType::InstagramVisitProfile,
+    EntryType::InstagramVisitProfileWithQuestion,
+    EntryType::CustomActionAskQuestion,
+    EntryType::CustomActionQuestion,
+    EntryType::CustomActionChooseOption,
+    EntryType::CustomActionVisitQuestion,
+    EntryType::CustomActionBlogComment,
+    EntryType::CustomActionBasic,
+    EntryType::CustomActionVisitAuto,
+    EntryType::CustomActionVisitDelay,
+    EntryType::CustomActionBonus,
+    EntryType::EmailSubscribe,
+    EntryType::FacebookEnter,
+    EntryType::FacebookVisitComplete,
+    EntryType::FacebookVisitLike,
+    EntryType::FacebookVisitWithQuestion,
+    EntryType::FacebookViewPost,
+    EntryType::PinterestVisitComplete,
+    EntryType::PinterestVisitFollow,
+    EntryType::TwitterEnter,
+    EntryType::TwitterRetweet,
+    EntryType::TwitterTweet,
+    EntryType::TwitterFollow,
+    EntryType::YoutubeVisitChannel,
+    EntryType::YoutubeVisitChannelWithDelay,
+    EntryType::YoutubeVisitChannelWithQuestion,
+    EntryType::YoutubeEnter,
+    EntryType::TwitchEnter,
+    EntryType::TwitchFollow,
+    EntryType::DiscordJoinServer,
+    EntryType::LinkedInFollow,
+    EntryType::SteamJoinGroup,
+    EntryType::Loyalty,
+    EntryType::ShareAction,
+];
 
+impl std::fmt::Display for EntryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            InstagramEnter => Enter,
-            InstagramViewPost => Simple(String("Done".to_string()), true, false),
-            InstagramVisitProfile => Simple(String("V".to_string()), false, false),
-            InstagramVisitProfileWithQuestion => Answer(",", 8),
-            CustomActionAskQuestion => Answer(",", 5),
-            CustomActionQuestion => Answer(",", 5),
-            CustomActionVisitQuestion => Answer(",", 5),
-            CustomActionChooseOption => Answer("\r\n", 5),
-            CustomActionBlogComment => TextInput,
-            CustomActionBasic => Simple(String("Done".to_string()), true, false),
-            CustomActionVisitAuto => Simple(String("V".to_string()), false, false),
-            CustomActionVisitDelay => SimpleWithDelay((String("V".to_string()), false, false), 7),
-            CustomActionBonus => Simple(Null, false, false),
-            EmailSubscribe => Simple(Null, false, false),
-            FacebookEnter => Enter,
-            FacebookVisitComplete => Simple(String("V".to_string()), false, false),
-            FacebookVisitLike => Simple(String("V".to_string()), true, false),
-            FacebookVisitWithQuestion => Answer(",", 6),
-            FacebookViewPost => Simple(Null, false, false),
-            PinterestVisitComplete => Simple(String("V".to_string()), false, false),
-            PinterestVisitFollow => Simple(String("V".to_string()), true, false),
-            TwitterEnter => Enter,
-            TwitterRetweet => RequestType::TwitterRetweet,
-            TwitterTweet => RequestType::TwitterTweet(true),
-            TwitterFollow => RequestType::TwitterFollow,
-            YoutubeVisitChannel => Simple(String("V".to_string()), false, false),
-            YoutubeVisitChannelWithQuestion => Answer(",", 6),
-            YoutubeVisitChannelWithDelay => SimpleWithDelay((String("V".to_string()), true, false), 4),
-            YoutubeEnter => Enter,
-            TwitchEnter => Enter,
-            TwitchFollow => Simple(Null, false, false),
-            LinkedInFollow => Simple(String("V".to_string()), false, false),
-            DiscordJoinServer => Unimplemented("The bot does not support Discord yet."),
-            SteamJoinGroup => Unimplemented("The bot does not support Steam groups auto-join yet."),
-            Loyalty => Unimplemented("The bot does not support loyalty entries yet."),
-            ShareAction => RequestType::TwitterTweet(false),
+            EntryType::Other(name) => write!(f, "{}", name),
+            entry_type => write!(f, "{:?}", entry_type),
         }
     }
 }
 
-pub enum RequestType {
-    Enter,
-    TextInput,
-    Answer(&'static str, u8),
-    Simple(serde_json::Value, bool, bool),
-    SimpleWithDelay((serde_json::Value, bool, bool), u8),
-    Unimplemented(&'static str),
-    TwitterRetweet,
-    TwitterTweet(bool), // true when tweet data is included, false when we must share
-    TwitterFollow,
+impl From<String> for EntryType {
+    fn from(name: String) -> EntryType {
+        BUILTIN_ENTRY_TYPES.iter().find(|entry_type| entry_type.to_string() == name).cloned().unwrap_or(EntryType::Other(name))
+    }
 }
 
-#[allow(dead_code)]
+impl From<EntryType> for String {
+    fn from(entry_type: EntryType) -> String {
+        entry_type.to_string()
+    }
+}
+
+/// Classify an entry method with the rules in use.
 pub fn classify(entry: &EntryMethod) -> Option<EntryType> {
-    use EntryType::*;
-
-    match entry {
-        entry if PINTEREST_VISIT_COMPLETE.matches(&entry).is_ok() => Some(PinterestVisitComplete),
-        entry if PINTEREST_VISIT_FOLLOW.matches(&entry).is_ok() => Some(PinterestVisitFollow),
-        entry if INSTAGRAM_ENTER.matches(&entry).is_ok() => Some(InstagramEnter),
-        entry if INSTAGRAM_VIEW_POST.matches(&entry).is_ok() => Some(InstagramViewPost),
-        entry if INSTAGRAM_VISIT_PROFILE.matches(&entry).is_ok() => Some(InstagramVisitProfile),
-        entry if INSTAGRAM_VISIT_PROFILE_WITH_QUESTION.matches(&entry).is_ok() => Some(InstagramVisitProfileWithQuestion),
-        entry if CUSTOM_ACTION_QUESTION.matches(&entry).is_ok() => Some(CustomActionQuestion),
-        entry if CUSTOM_ACTION_ASK_QUESTION.matches(&entry).is_ok() => Some(CustomActionAskQuestion),
-        entry if CUSTOM_ACTION_VISIT_QUESTION.matches(&entry).is_ok() => Some(CustomActionVisitQuestion),
-        entry if CUSTOM_ACTION_CHOOSE_OPTION.matches(&entry).is_ok() => Some(CustomActionChooseOption),
-        entry if CUSTOM_ACTION_BLOG_COMMENT.matches(&entry).is_ok() => Some(CustomActionBlogComment),
-        entry if CUSTOM_ACTION_BASIC.matches(&entry).is_ok() => Some(CustomActionBasic),
-        entry if CUSTOM_ACTION_VISIT_AUTO.matches(&entry).is_ok() => Some(CustomActionVisitAuto),
-        entry if CUSTOM_ACTION_VISIT_DELAY.matches(&entry).is_ok() => Some(CustomActionVisitDelay),
-        entry if CUSTOM_ACTION_BONUS.matches(&entry).is_ok() => Some(CustomActionBonus),
-        entry if EMAIL_SUBSCRIBE.matches(&entry).is_ok() => Some(EmailSubscribe),
-        entry if FACEBOOK_ENTER.matches(&entry).is_ok() => Some(FacebookEnter),
-        entry if FACEBOOK_VISIT_COMPLETE.matches(&entry).is_ok() => Some(FacebookVisitComplete),
-        entry if FACEBOOK_VISIT_LIKE_WITH_QUESTION.matches(&entry).is_ok() => Some(FacebookVisitWithQuestion),
-        entry if FACEBOOK_VISIT_LIKE.matches(&entry).is_ok() => Some(FacebookVisitLike),
-        entry if FACEBOOK_VIEW_POST.matches(&entry).is_ok() => Some(FacebookViewPost),
-        entry if TWITTER_ENTER.matches(&entry).is_ok() => Some(TwitterEnter),
-        entry if TWITTER_RETWEET.matches(&entry).is_ok() => Some(TwitterRetweet),
-        entry if TWITTER_TWEET.matches(&entry).is_ok() => Some(TwitterTweet),
-        entry if TWITTER_FOLLOW.matches(&entry).is_ok() => Some(TwitterFollow),
-        entry if YOUTUBE_VISIT_CHANNEL.matches(&entry).is_ok() => Some(YoutubeVisitChannel),
-        entry if YOUTUBE_VISIT_CHANNEL_WITH_QUESTION.matches(&entry).is_ok() => Some(YoutubeVisitChannelWithQuestion),
-        entry if YOUTUBE_VISIT_CHANNEL_WITH_DELAY.matches(&entry).is_ok() => Some(YoutubeVisitChannelWithDelay),
-        entry if YOUTUBE_ENTER.matches(&entry).is_ok() => Some(YoutubeEnter),
-        entry if TWITCHTV_ENTER.matches(&entry).is_ok() => Some(TwitchEnter),
-        entry if TWITCHTV_FOLLOW.matches(&entry).is_ok() => Some(TwitchFollow),
-        entry if DISCORD_JOIN_SERVER.matches(&entry).is_ok() => Some(DiscordJoinServer),
-        entry if LINKEDIN_FOLLOW.matches(&entry).is_ok() => Some(LinkedInFollow),
-        entry if STEAM_JOIN_GROUP.matches(&entry).is_ok() => Some(SteamJoinGroup),
-        entry if LOYALTY.matches(&entry).is_ok() => Some(Loyalty),
-        entry if SHARE_ACTION.matches(&entry).is_ok() => Some(ShareAction),
-        _ => None
-    }
-}
\ No newline at end of file
+    rules().classify(entry)
+}
+
+/// All the entry types an entry method matches with the rules in use, by priority.
+pub fn matching(entry: &EntryMethod) -> Vec<EntryType> {
+    rules().matching(entry)
+}
+
+/// How close an entry method is to an entry type.
+#[derive(Debug)]
+pub struct Explanation {
+    pub entry_type: EntryType,
+    /// The number of fields which matched
+    pub matched: usize,
+    /// The number of fields checked
+    pub fields: usize,
+    /// The fields which did not match, with the reason
+    pub mismatches: Vec<(&'static str, &'static str)>,
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}/{} fields match", self.entry_type, self.matched, self.fields)?;
+        let mismatches = self.mismatches.iter().map(|(field, reason)| format!("{}: {}", field, reason)).collect::<Vec<String>>();
+        if !mismatches.is_empty() {
+            write!(f, " ({})", mismatches.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare an entry method with all the verifiers of the rules in use, the closest first.
+pub fn classify_explained(entry: &EntryMethod) -> Vec<Explanation> {
+    rules().classify_explained(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parsing::parse_html, strictness::Strictness};
+
+    #[test]
+    fn explanations() {
+        let html = std::fs::read_to_string("test_data/corpus/unclassified.html").unwrap();
+        let (mut giveaway, _, _) = parse_html(&html, Strictness::Lenient).unwrap();
+
+        // A Twitter follow without the id of the account
+        let explanations = classify_explained(&giveaway.entry_methods[1]);
+        assert_eq!(classify(&giveaway.entry_methods[1]), None);
+        assert_eq!(explanations[0].entry_type, EntryType::TwitterFollow);
+        assert_eq!(explanations[0].matched, FIELD_COUNT - 1);
+        assert_eq!(explanations[0].mismatches, vec![("2", "Expected Number, found Null")]);
+        assert_eq!(explanations[0].to_string(), "TwitterFollow: 12/13 fields match (2: Expected Number, found Null)");
+        assert!(explanations.windows(2).all(|w| w[0].mismatches.len() <= w[1].mismatches.len()));
+
+        // Classified entry methods match completely
+        let explanations = classify_explained(&giveaway.entry_methods[2]);
+        assert_eq!(classify(&giveaway.entry_methods[2]), Some(explanations[0].entry_type.clone()));
+        assert!(explanations[0].mismatches.is_empty());
+
+        // An Instagram entry linking to another website
+        let instagram_enter = &mut giveaway.entry_methods[2];
+        instagram_enter.config1 = Some(String::from("https://example.net/examplebrand"));
+        assert_eq!(classify(instagram_enter), None);
+        assert_eq!(classify_explained(instagram_enter)[0].mismatches, vec![("1", "Unexpected host")]);
+    }
+
+    #[test]
+    fn builtin_rules() {
+        let rules = RuleSet::builtin();
+        assert_eq!(rules.rules.len(), BUILTIN_ENTRY_TYPES.len());
+        for entry_type in BUILTIN_ENTRY_TYPES.iter() {
+            assert!(rules.rules.iter().any(|rule| &rule.name == entry_type), "no rule for {}", entry_type);
+        }
+
+        // The same rules in JSON
+        let json = serde_json::to_string(&rules).unwrap();
+        let from_json = RuleSet::from_json(&json).unwrap();
+        assert!(rules.rules.iter().zip(from_json.rules.iter()).all(|(a, b)| a.name == b.name && a.same_checks(b)));
+    }
+
+    #[test]
+    fn custom_rules() {
+        let html = std::fs::read_to_string("test_data/corpus/unclassified.html").unwrap();
+        let (giveaway, _, _) = parse_html(&html, Strictness::Lenient).unwrap();
+        let tiktok_follow = &giveaway.entry_methods[0];
+        assert_eq!(tiktok_follow.entry_type, "tiktok_follow");
+        assert_eq!(classify(tiktok_follow), None);
+
+        let mut rules = RuleSet::builtin();
+        let custom_rule = r#"
+            [[rules]]
+            name = "TiktokFollow"
+            entry_type = "tiktok_follow"
+            workflow = "Anything"
+            template = "Anything"
+            method_type = "Anything"
+            configs = ["IsNotEmpty", "Anything", "Anything", "Anything", "Anything", "Anything", "Anything", "Anything", "Anything"]
+        "#;
+        let mut custom = RuleSet::from_toml(custom_rule).unwrap();
+        rules.rules.append(&mut custom.rules);
+        assert_eq!(rules.classify(tiktok_follow), Some(EntryType::Other(String::from("TiktokFollow"))));
+        assert_eq!(rules.classify(&giveaway.entry_methods[2]), classify(&giveaway.entry_methods[2]));
+
+        // A looser rule after it overlaps
+        let mut looser = RuleSet::from_toml(&custom_rule.replace("TiktokFollow", "AnyTiktok").replace("\"IsNotEmpty\"", "\"Anything\"")).unwrap();
+        rules.rules.append(&mut looser.rules);
+        assert_eq!(rules.classify(tiktok_follow), Some(EntryType::Other(String::from("TiktokFollow"))));
+        assert_eq!(rules.matching(tiktok_follow), vec![EntryType::Other(String::from("TiktokFollow")), EntryType::Other(String::from("AnyTiktok"))]);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let rule = |name: &str, config1: &str| format!(r#"
+            [[rules]]
+            name = "{}"
+            entry_type = "custom_action"
+            workflow = "Anything"
+            template = "Anything"
+            method_type = "Anything"
+            configs = [{}, "Anything", "Anything", "Anything", "Anything", "Anything", "Anything", "Anything", "Anything"]
+        "#, name, config1);
+
+        assert!(matches!(RuleSet::from_toml("rules = []"), Err(RuleError::Invalid(0, _))));
+        assert!(matches!(RuleSet::from_toml(&rule("Empty", "{ IsIn = [] }")), Err(RuleError::Invalid(0, _))));
+        assert!(matches!(RuleSet::from_toml(&rule("Unknown", "\"IsAnEmail\"")), Err(RuleError::Toml(_))));
+        assert!(matches!(RuleSet::from_toml(&rule("", "\"Exists\"")), Err(RuleError::Invalid(0, _))));
+
+        let shadowed = format!("{}{}", rule("First", "{ Is = \"a\" }"), rule("Second", "{ Is = \"a\" }"));
+        match RuleSet::from_toml(&shadowed) {
+            Err(e @ RuleError::Invalid(1, _)) => assert_eq!(e.to_string(), "invalid rule #2: it can never match, rule #1 checks the same things (Second)"),
+            other => panic!("the second rule should be shadowed, got {:?}", other.map(|rules| rules.rules.len())),
+        }
+        assert!(RuleSet::from_toml(&format!("{}{}", rule("First", "{ Is = \"a\" }"), rule("Second", "{ Is = \"b\" }"))).is_ok());
+
+        assert!(matches!(RuleSet::from_toml(&rule("Regex", "{ Matches = \"(unclosed\" }")), Err(RuleError::Toml(_))));
+        assert!(matches!(RuleSet::from_toml(&rule("Hosts", "{ IsUrlOf = [] }")), Err(RuleError::Invalid(0, _))));
+        assert!(matches!(RuleSet::from_toml(&rule("And", "{ Not = { And = [\"Exists\"] } }")), Err(RuleError::Invalid(0, _))));
+    }
+
+    #[test]
+    fn no_overlaps() {
+        let mut pages = std::fs::read_dir("test_data/corpus").unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map(|e| e == "html").unwrap_or(false))
+            .collect::<Vec<_>>();
+        pages.sort();
+
+        let mut overlaps = Vec::new();
+        for page in &pages {
+            let html = std::fs::read_to_string(page).unwrap();
+            let giveaway = match parse_html(&html, Strictness::Lenient) {
+                Ok((giveaway, _, _)) => giveaway,
+                Err(_) => continue,
+            };
+            for entry_method in &giveaway.entry_methods {
+                let entry_types = matching(entry_method);
+                if entry_types.len() > 1 {
+                    overlaps.push(format!("{} {}: {:?}", page.display(), entry_method.id, entry_types));
+                }
+            }
+        }
+        assert!(overlaps.is_empty(), "entry methods matching several rules:\n{}", overlaps.join("\n"));
+    }
+
+    #[test]
+    fn matchers() {
+        let matches = |arg: &Arg, value: &str| arg.matches(Some(&value.to_string())).is_ok();
+
+        assert!(matches(&Arg::IsUrl, "https://twitter.com/example"));
+        assert!(matches(&Arg::IsUrl, "steam://url/GroupSteamIDPage/1"));
+        assert!(!matches(&Arg::IsUrl, "twitter.com/example"));
+        assert!(!matches(&Arg::IsUrl, "javascript:alert(1)"));
+        assert!(!matches(&Arg::IsUrl, ""));
+
+        assert!(matches(&Arg::IsHttpUrl, "https://twitter.com/example"));
+        assert!(!matches(&Arg::IsHttpUrl, "twitter.com/example"));
+        assert!(!matches(&Arg::IsHttpUrl, "javascript:alert(1)"));
+        assert!(!matches(&Arg::IsHttpUrl, "Visit the page"));
+
+        let twitter = Arg::IsUrlOf(vec![String::from("twitter.com"), String::from("X.com")]);
+        assert!(matches(&twitter, "https://twitter.com/example/status/1"));
+        assert!(matches(&twitter, "http://mobile.twitter.com/example"));
+        assert!(matches(&twitter, "https://x.com/example"));
+        assert!(!matches(&twitter, "https://nottwitter.com/example"));
+        assert!(!matches(&twitter, "https://twitter.com.example.net/"));
+
+        let pattern: Arg = serde_json::from_str(r#"{"Matches": "^[0-9]+/[0-9]+$"}"#).unwrap();
+        assert!(matches(&pattern, "12/34"));
+        assert!(!matches(&pattern, "12/34/56"));
+        assert_eq!(pattern.matches(None), Err("Expected String, found Null"));
+
+        let delay = Arg::And(vec![Arg::AtLeast(5), Arg::AtMost(60)]);
+        assert!(matches(&delay, "30"));
+        assert!(!matches(&delay, "4"));
+        assert_eq!(delay.matches(Some(&String::from("61"))), Err("Number too large"));
+        assert!(!matches(&delay, "thirty"));
+
+        let not_empty = Arg::Not(Box::new(Arg::IsEmpty));
+        assert!(matches(&not_empty, "a"));
+        assert!(!matches(&not_empty, ""));
+        assert!(not_empty.matches(None).is_ok());
+
+        // JSON checks
+        let html = std::fs::read_to_string("test_data/corpus/unclassified.html").unwrap();
+        let (giveaway, _, _) = parse_html(&html, Strictness::Lenient).unwrap();
+        let tiktok_follow = &giveaway.entry_methods[0];
+        let check = |json: &str| serde_json::from_str::<JsonCheck>(json).unwrap().matches(tiktok_follow);
+        assert!(check(r#"{"field": "config_selections", "kind": "Array"}"#).is_ok());
+        assert!(check(r#"{"field": "config_selections", "pointer": "/0", "arg": "Lacks"}"#).is_ok());
+        assert!(check(r#"{"field": "config", "kind": "Object", "arg": {"Is": "{}"}}"#).is_ok());
+        assert_eq!(check(r#"{"field": "config", "kind": "Array"}"#), Err("Unexpected JSON type"));
+        assert_eq!(check(r#"{"field": "config", "pointer": "/id", "arg": "Exists"}"#), Err("Expected something, found Null"));
+    }
+}
@@ -1,9 +1,8 @@
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
-use crate::entry::ValidatedEntry;
+use crate::{entry::ValidatedEntry, strictness::*};
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitContestant {
@@ -14,20 +13,29 @@ pub struct InitContestant {
     pub trigger_auto_opt_in: bool,
     pub allow_autoticking: bool,
     pub amoeRequired: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectUnknownFields for InitContestant {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("InitContestant", &self.extra);
+        if let MaybeUninitContestant::Connected(contestant) = &self.contestant {
+            contestant.collect_unknown_fields(report);
+        }
+    }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MaybeUninitContestant {
-    Connected(Contestant),
+    Connected(Box<Contestant>),
     Disconnected {
         entered: Value,
         claims: Value
     }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Contestant {
     pub auth_key: Option<String>,
@@ -44,9 +52,22 @@ pub struct Contestant {
     pub stored_dob: Option<String>,
     pub viral_share_paths: HashMap<String, String>,
     pub send_confirmation: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectUnknownFields for Contestant {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("Contestant", &self.extra);
+        for authentification in &self.authentications {
+            report.add("Authentification", &authentification.extra);
+        }
+        for entry in self.entered.values().flatten() {
+            entry.collect_unknown_fields(report);
+        }
+    }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Authentification {
     #[serde(default)]
@@ -59,12 +80,21 @@ pub struct Authentification {
     pub uid: String,
     pub updated_at: String,
     pub url: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Winner {
-    entry_number: usize,
-    name: String,
-    image: String,
+    pub entry_number: usize,
+    pub name: String,
+    pub image: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectUnknownFields for Winner {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("Winner", &self.extra);
+    }
 }
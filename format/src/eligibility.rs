@@ -0,0 +1,250 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use crate::campaign::Campaign;
+
+/// The countries we look for in the terms: ISO code, names and adjectives (not case sensitive) and abbreviations (case sensitive).
+/// Regions are not listed, as excluding a region of a country does not exclude the whole country.
+const COUNTRIES: [(&str, &[&str], &[&str]); 29] = [
+    ("US", &["united states", "estados unidos", "états-unis", "etats-unis"], &["US", "USA", "U.S.", "U.S.A."]),
+    ("GB", &["united kingdom", "great britain", "british", "royaume-uni", "reino unido"], &["UK", "U.K.", "GB"]),
+    ("CA", &["canada", "canadian"], &[]),
+    ("AU", &["australia", "australian"], &[]),
+    ("NZ", &["new zealand"], &["NZ"]),
+    ("IE", &["ireland", "irish"], &[]),
+    ("DE", &["germany", "german", "deutschland"], &[]),
+    ("FR", &["france", "french"], &[]),
+    ("ES", &["spain", "spanish", "españa"], &[]),
+    ("IT", &["italy", "italian", "italia"], &[]),
+    ("NL", &["netherlands", "dutch"], &[]),
+    ("BE", &["belgium", "belgian", "belgique"], &[]),
+    ("CH", &["switzerland", "swiss", "suisse", "schweiz"], &[]),
+    ("AT", &["austria", "austrian", "österreich"], &[]),
+    ("SE", &["sweden", "swedish"], &[]),
+    ("NO", &["norway", "norwegian"], &[]),
+    ("DK", &["denmark", "danish"], &[]),
+    ("FI", &["finland", "finnish"], &[]),
+    ("PL", &["poland", "polska"], &[]),
+    ("PT", &["portugal", "portuguese"], &[]),
+    ("BR", &["brazil", "brazilian", "brasil"], &[]),
+    ("MX", &["mexico", "mexican", "méxico"], &[]),
+    ("IN", &["india"], &[]),
+    ("JP", &["japan", "japanese"], &[]),
+    ("KR", &["south korea", "korean"], &[]),
+    ("PH", &["philippines", "filipino"], &[]),
+    ("SG", &["singapore"], &[]),
+    ("MY", &["malaysia", "malaysian"], &[]),
+    ("ZA", &["south africa", "south african"], &[]),
+];
+
+/// The ages accepted as minimum ages, to avoid reading other numbers as ages.
+const AGES: &str = "(1[3-9]|2[0-5])";
+
+/// Who can enter a giveaway, read from its terms and settings.
+/// The countries and the age are guessed from the text, so an empty list or no age means that we do not know.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Eligibility {
+    /// The terms say the giveaway is open worldwide
+    #[serde(default)]
+    pub worldwide: bool,
+    /// ISO codes of the countries the giveaway is limited to
+    #[serde(default)]
+    pub allowed_countries: Vec<String>,
+    /// ISO codes of the countries which can't enter
+    #[serde(default)]
+    pub excluded_countries: Vec<String>,
+    #[serde(default)]
+    pub minimum_age: Option<u8>,
+    /// Contestants must log in before seeing the entry methods
+    #[serde(default)]
+    pub login_first: bool,
+    /// Contestants can't log in with an email address and need a social account
+    #[serde(default)]
+    pub social_login_required: bool,
+}
+
+struct Patterns {
+    countries: Vec<(&'static str, Regex)>,
+    restriction: Regex,
+    exclusion: Regex,
+    worldwide: Regex,
+    ages: [Regex; 3],
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let countries = COUNTRIES.iter().map(|(code, names, abbreviations)| {
+            let mut alternatives = vec![format!(r"(?i:\b(?:{})\b)", names.iter().map(|name| regex::escape(name)).collect::<Vec<String>>().join("|"))];
+            for abbreviation in abbreviations.iter() {
+                let end = if abbreviation.ends_with('.') { "" } else { r"\b" };
+                alternatives.push(format!(r"\b{}{}", regex::escape(abbreviation), end));
+            }
+            (*code, Regex::new(&alternatives.join("|")).unwrap())
+        }).collect();
+
+        Patterns {
+            countries,
+            restriction: Regex::new(r"(?i)\b(open|available|eligible|valid|limited|restricted|only|residents?|citizens?|reside|living|void|réservée?|ouvert|résidents?|abierto|residentes|solo|nur|wohnsitz)\b").unwrap(),
+            exclusion: Regex::new(r"(?i)\b(except|excluding|excluded|exclude|not (open|available|eligible|valid)|void|sauf|excepto|ausgenommen)\b").unwrap(),
+            worldwide: Regex::new(r"(?i)\b(world\s*-?\s*wide|internationally|all countries|monde entier|mundial|weltweit)\b").unwrap(),
+            ages: [
+                Regex::new(&format!(r"(?i)\b{}\s*(\+|years?\s+(of\s+age|old)|yrs?\b|ans\b|años\b|jahre\b)", AGES)).unwrap(),
+                Regex::new(&format!(r"(?i)\b(aged?|age of|minimum age( of)?|must be (over|at least)|âgée?s? de|mayores de|mindestens)\s*:?\s*{}\b", AGES)).unwrap(),
+                Regex::new(&format!(r"(?i)\b{}\s*(or|and)\s*(older|over|above|up)\b", AGES)).unwrap(),
+            ],
+        }
+    })
+}
+
+/// Split a text in sentences, without splitting abbreviations like "U.S.".
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let end_of_sentence = match c {
+            '\n' | '!' | '?' | ';' => true,
+            '.' => {
+                let next_is_space = chars.peek().map(|(_, next)| next.is_whitespace()).unwrap_or(true);
+                let word = text[start..index].rsplit(char::is_whitespace).next().unwrap_or_default();
+                next_is_space && word.chars().count() > 1 && !word.contains('.')
+            }
+            _ => false,
+        };
+        if end_of_sentence {
+            sentences.push(&text[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    sentences.push(&text[start..]);
+    sentences.into_iter().filter(|sentence| !sentence.trim().is_empty()).collect()
+}
+
+fn collect_strings<'a>(value: &'a Value, strings: &mut Vec<&'a str>) {
+    match value {
+        Value::String(string) => strings.push(string),
+        Value::Array(values) => values.iter().for_each(|value| collect_strings(value, strings)),
+        Value::Object(values) => values.values().for_each(|value| collect_strings(value, strings)),
+        _ => (),
+    }
+}
+
+impl Eligibility {
+    /// Read the countries and the minimum age in a text.
+    pub fn read_text(&mut self, text: &str) {
+        let patterns = patterns();
+
+        for sentence in sentences(text) {
+            if patterns.worldwide.is_match(sentence) {
+                self.worldwide = true;
+            }
+            if !patterns.restriction.is_match(sentence) {
+                continue;
+            }
+            let exclusion_start = patterns.exclusion.find(sentence).map(|exclusion| exclusion.start());
+            for (code, pattern) in &patterns.countries {
+                for found in pattern.find_iter(sentence) {
+                    let countries = match exclusion_start {
+                        Some(start) if found.start() > start => &mut self.excluded_countries,
+                        _ => &mut self.allowed_countries,
+                    };
+                    if !countries.iter().any(|country| country == code) {
+                        countries.push(code.to_string());
+                    }
+                }
+            }
+        }
+        // "Open to Ireland, excluding Northern Ireland" does not exclude Ireland
+        let allowed_countries = &self.allowed_countries;
+        self.excluded_countries.retain(|country| !allowed_countries.contains(country));
+        self.allowed_countries.sort();
+        self.excluded_countries.sort();
+
+        for pattern in &patterns.ages {
+            for captures in pattern.captures_iter(text) {
+                let age = captures.iter().flatten().filter_map(|group| group.as_str().parse::<u8>().ok()).next();
+                if age > self.minimum_age {
+                    self.minimum_age = age;
+                }
+            }
+        }
+    }
+}
+
+impl From<&Campaign> for Eligibility {
+    fn from(campaign: &Campaign) -> Eligibility {
+        let mut eligibility = Eligibility {
+            login_first: campaign.login_first,
+            social_login_required: !campaign.login_types.is_empty() && !campaign.login_types.iter().any(|login_type| login_type == "email"),
+            ..Eligibility::default()
+        };
+
+        let mut texts = vec![campaign.terms_and_conditions.as_str()];
+        collect_strings(&campaign.messages, &mut texts);
+        collect_strings(&campaign.contestant_details_groups, &mut texts);
+        for text in texts {
+            eligibility.read_text(text);
+        }
+        eligibility
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> Eligibility {
+        let mut eligibility = Eligibility::default();
+        eligibility.read_text(text);
+        eligibility
+    }
+
+    #[test]
+    fn countries() {
+        let eligibility = read("Open worldwide. One prize per household.");
+        assert!(eligibility.worldwide);
+        assert!(eligibility.allowed_countries.is_empty());
+
+        let eligibility = read("This giveaway is open to U.S. residents only. Void where prohibited.");
+        assert!(!eligibility.worldwide);
+        assert_eq!(eligibility.allowed_countries, vec!["US"]);
+        assert!(eligibility.excluded_countries.is_empty());
+
+        let eligibility = read("Open to residents of the United Kingdom and Ireland, excluding Scotland.\nThe prize ships from Germany.");
+        assert_eq!(eligibility.allowed_countries, vec!["GB", "IE"]);
+        assert!(eligibility.excluded_countries.is_empty());
+
+        let eligibility = read("Open to residents of Ireland, excluding Northern Ireland and the United States.");
+        assert_eq!(eligibility.allowed_countries, vec!["IE"]);
+        assert_eq!(eligibility.excluded_countries, vec!["US"]);
+
+        let eligibility = read("Open internationally except Canada and Mexico");
+        assert!(eligibility.worldwide);
+        assert_eq!(eligibility.excluded_countries, vec!["CA", "MX"]);
+
+        let eligibility = read("Concours réservé aux résidents de France métropolitaine et de Belgique.");
+        assert_eq!(eligibility.allowed_countries, vec!["BE", "FR"]);
+
+        // Not restrictions
+        assert_eq!(read("Follow US on Twitter. Contact us in Germany.").allowed_countries, Vec::<String>::new());
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(read("You must be 18 years of age or older to enter.").minimum_age, Some(18));
+        assert_eq!(read("Entrants must be 21+ (18+ in Canada).").minimum_age, Some(21));
+        assert_eq!(read("Minimum age: 16").minimum_age, Some(16));
+        assert_eq!(read("Must be over 13.").minimum_age, Some(13));
+        assert_eq!(read("Participants âgés de 18 ans minimum").minimum_age, Some(18));
+        assert_eq!(read("18 and over, one entry per person").minimum_age, Some(18));
+        assert_eq!(read("Win 1 of 20 prizes, at least 15 entries are needed").minimum_age, None);
+        assert_eq!(read("Open worldwide. One prize per household.").minimum_age, None);
+    }
+
+    #[test]
+    fn sentence_splitting() {
+        assert_eq!(sentences("Open to U.S. residents. Ends soon!\nNo purchase"), vec!["Open to U.S. residents", " Ends soon", "No purchase"]);
+    }
+}
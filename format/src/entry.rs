@@ -1,16 +1,23 @@
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+use crate::strictness::*;
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ValidatedEntry {
     c: usize,
     t: u64,
     ts: Value,
     w: usize,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectUnknownFields for ValidatedEntry {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("ValidatedEntry", &self.extra);
+    }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EntryMethod {
     pub id: String,
@@ -72,10 +79,35 @@ pub struct EntryMethod {
     pub paid: bool,
     pub action_description: String,
     pub share_suffix: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+impl EntryMethod {
+    /// Get one of `config1` to `config9`.
+    pub fn config(&self, index: usize) -> Option<&String> {
+        match index {
+            1 => self.config1.as_ref(),
+            2 => self.config2.as_ref(),
+            3 => self.config3.as_ref(),
+            4 => self.config4.as_ref(),
+            5 => self.config5.as_ref(),
+            6 => self.config6.as_ref(),
+            7 => self.config7.as_ref(),
+            8 => self.config8.as_ref(),
+            9 => self.config9.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl CollectUnknownFields for EntryMethod {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("EntryMethod", &self.extra);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IncompleteEntryMethod {
     pub type_without_provider: String,
     pub worth: usize,
@@ -1,9 +1,8 @@
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
-use crate::{prelude::*, incentive::IncompleteIncentive, entry::IncompleteEntryMethod, campaign::IncompleteCampaign};
+use crate::{prelude::*, strictness::*, incentive::IncompleteIncentive, entry::IncompleteEntryMethod, campaign::IncompleteCampaign, taxonomy::Taxonomy, eligibility::Eligibility};
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Giveaway {
@@ -14,10 +13,22 @@ pub struct Giveaway {
     #[serde(rename = "entry_methods")]
     pub entry_methods: Vec<EntryMethod>,
     pub incentive: Incentive,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+impl CollectUnknownFields for Giveaway {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("Giveaway", &self.extra);
+        self.campaign.collect_unknown_fields(report);
+        for entry_method in &self.entry_methods {
+            entry_method.collect_unknown_fields(report);
+        }
+        self.incentive.collect_unknown_fields(report);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IncompleteGiveaway {
     #[serde(flatten)]
@@ -26,11 +37,17 @@ pub struct IncompleteGiveaway {
     pub entry_methods: Vec<IncompleteEntryMethod>,
     #[serde(flatten)]
     pub incentive: IncompleteIncentive,
+    #[serde(flatten)]
+    pub taxonomy: Taxonomy,
+    #[serde(flatten)]
+    pub eligibility: Eligibility,
 }
 
 impl From<Giveaway> for IncompleteGiveaway {
     fn from(giveaway: Giveaway) -> IncompleteGiveaway {
         IncompleteGiveaway {
+            taxonomy: Taxonomy::from(giveaway.entry_methods.as_slice()),
+            eligibility: Eligibility::from(&giveaway.campaign),
             campaign: giveaway.campaign.into(),
             entry_methods: {
                 let mut incomplete_entry_methods = Vec::new();
@@ -44,12 +61,17 @@ impl From<Giveaway> for IncompleteGiveaway {
     }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     #[serde(flatten)]
     pub giveaway: IncompleteGiveaway,
     pub last_updated: u64,
+    /// When the last update failed, if it failed after the last successful update
+    #[serde(default)]
+    pub failed_at: Option<u64>,
+    /// The number of updates which failed since the last successful update
+    #[serde(default)]
+    pub failures: usize,
     pub referers: Vec<String>,
     pub entry_count: Option<usize>,
     pub entry_evolution: Option<HashMap<String, usize>>,
@@ -82,10 +104,16 @@ impl std::ops::Add for SearchResult {
             None
         };
 
+        let last_updated = self.last_updated.max(rhs.last_updated);
+        let failed_at = self.failed_at.max(rhs.failed_at).filter(|failed_at| *failed_at > last_updated);
+        let failures = if failed_at.is_some() { self.failures.max(rhs.failures) } else { 0 };
+
         if self.last_updated > rhs.last_updated {
             SearchResult {
                 giveaway: self.giveaway,
                 last_updated: self.last_updated,
+                failed_at,
+                failures,
                 referers,
                 entry_count: self.entry_count,
                 entry_evolution,
@@ -94,6 +122,8 @@ impl std::ops::Add for SearchResult {
             SearchResult {
                 giveaway: rhs.giveaway,
                 last_updated: rhs.last_updated,
+                failed_at,
+                failures,
                 referers,
                 entry_count: rhs.entry_count,
                 entry_evolution,
@@ -142,6 +172,7 @@ fn test() {
                 campaign_type: String::from(""),
                 terms_and_conditions: String::from("don't cheat"),
                 announce_winners: true,
+                winners: None,
             },
             entry_methods: Vec::new(),
             incentive: IncompleteIncentive {
@@ -149,8 +180,12 @@ fn test() {
                 description: String::from("a beautiful game"),
                 incentive_type: String::from("Prize")
             },
+            taxonomy: Taxonomy::default(),
+            eligibility: Eligibility::default(),
         },
         last_updated: 0,
+        failed_at: None,
+        failures: 0,
         referers: Vec::new(),
         entry_count: None,
         entry_evolution: None,
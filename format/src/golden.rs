@@ -0,0 +1,82 @@
+//! Regression tests on a corpus of anonymized gleam.io pages.
+//!
+//! Each page of `test_data/corpus` is parsed, its entry methods are classified and typed, and it is converted to an `IncompleteGiveaway`.
+//! The result is compared with the `.golden.json` file next to the page.
+//! When the output legitimately changes, run `UPDATE_GOLDENS=1 cargo test -p format golden` and review the diff of the golden files.
+
+use crate::{classifier::classify, giveaway::IncompleteGiveaway, parsing::parse_html, strictness::*, typed_entry::TypedEntry};
+use serde_json::{json, Value};
+use std::{convert::TryFrom, fs, path::Path};
+
+const CORPUS: &str = "test_data/corpus";
+
+/// Everything we get from a page.
+fn observe(html: &str) -> Value {
+    let (giveaway, init_contestant, entry_count) = match parse_html(html, Strictness::Lenient) {
+        Ok(page) => page,
+        Err(e) => return json!({ "error": e.to_string() }),
+    };
+
+    let mut unknown_fields = giveaway.unknown_fields();
+    init_contestant.collect_unknown_fields(&mut unknown_fields);
+    let entry_methods = giveaway.entry_methods.iter().map(|entry_method| json!({
+        "id": entry_method.id,
+        "entry_type": entry_method.entry_type,
+        "classified_as": classify(entry_method).map(|entry_type| format!("{:?}", entry_type)),
+        "typed": match TypedEntry::try_from(entry_method) {
+            Ok(typed_entry) => json!(typed_entry),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+    })).collect::<Vec<Value>>();
+
+    json!({
+        "entry_count": entry_count,
+        "unknown_fields": unknown_fields.to_string(),
+        "entry_methods": entry_methods,
+        "incomplete": IncompleteGiveaway::from(giveaway),
+    })
+}
+
+#[test]
+fn golden() {
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some();
+    let mut pages = fs::read_dir(CORPUS).expect("Can't read the corpus")
+        .map(|entry| entry.expect("Can't read the corpus").path())
+        .filter(|path| path.extension().map(|e| e == "html").unwrap_or(false))
+        .collect::<Vec<_>>();
+    pages.sort();
+    assert!(!pages.is_empty(), "the corpus is empty");
+
+    let mut failures = Vec::new();
+    for page in &pages {
+        let html = fs::read_to_string(page).expect("Can't read page");
+        let observed = observe(&html);
+        let golden_path = page.with_extension("golden.json");
+
+        if update {
+            let mut data = serde_json::to_string_pretty(&observed).unwrap();
+            data.push('\n');
+            fs::write(&golden_path, data).expect("Can't write golden file");
+            continue;
+        }
+
+        match fs::read_to_string(&golden_path) {
+            Ok(golden) if serde_json::from_str::<Value>(&golden).expect("Invalid golden file") == observed => (),
+            Ok(_) => failures.push(format!("{} changed, got:\n{}", page.display(), serde_json::to_string_pretty(&observed).unwrap())),
+            Err(_) => failures.push(format!("{} has no golden file", page.display())),
+        }
+    }
+
+    // Goldens of deleted pages
+    for entry in fs::read_dir(CORPUS).expect("Can't read the corpus") {
+        let path = entry.expect("Can't read the corpus").path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if let Some(page) = name.strip_suffix(".golden.json") {
+            if !Path::new(CORPUS).join(format!("{}.html", page)).exists() {
+                failures.push(format!("{} has no page", path.display()));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}\n\nRun `UPDATE_GOLDENS=1 cargo test -p format golden` to accept the changes.", failures.join("\n\n"));
+}
@@ -0,0 +1,285 @@
+//! A small HTML reader, sufficient to find the data embedded in gleam.io pages.
+
+/// Named character references, except the Latin-1 ones which are in `LATIN_1`.
+const ENTITIES: [(&str, u32); 51] = [
+    ("quot", 34), ("amp", 38), ("apos", 39), ("lt", 60), ("gt", 62),
+    ("OElig", 338), ("oelig", 339), ("Scaron", 352), ("scaron", 353), ("Yuml", 376), ("fnof", 402), ("circ", 710), ("tilde", 732),
+    ("ensp", 8194), ("emsp", 8195), ("thinsp", 8201), ("zwnj", 8204), ("zwj", 8205), ("lrm", 8206), ("rlm", 8207),
+    ("ndash", 8211), ("mdash", 8212), ("lsquo", 8216), ("rsquo", 8217), ("sbquo", 8218), ("ldquo", 8220), ("rdquo", 8221), ("bdquo", 8222),
+    ("dagger", 8224), ("Dagger", 8225), ("bull", 8226), ("hellip", 8230), ("permil", 8240), ("prime", 8242), ("Prime", 8243),
+    ("lsaquo", 8249), ("rsaquo", 8250), ("euro", 8364), ("trade", 8482),
+    ("larr", 8592), ("uarr", 8593), ("rarr", 8594), ("darr", 8595), ("harr", 8596),
+    ("minus", 8722), ("infin", 8734), ("ne", 8800), ("le", 8804), ("ge", 8805), ("hearts", 9829), ("diams", 9830),
+];
+
+/// The names of the characters from U+00A0 to U+00FF.
+const LATIN_1: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf", "laquo", "not", "shy", "reg", "macr",
+    "deg", "plusmn", "sup2", "sup3", "acute", "micro", "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute", "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml",
+    "ETH", "Ntilde", "Ograve", "Oacute", "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute", "THORN", "szlig",
+    "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil", "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml",
+    "eth", "ntilde", "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc", "uuml", "yacute", "thorn", "yuml",
+];
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        return Some(std::char::from_u32(code).filter(|c| *c != '\0').unwrap_or('\u{FFFD}'));
+    }
+
+    if let Some(idx) = LATIN_1.iter().position(|name| *name == entity) {
+        return std::char::from_u32(0xA0 + idx as u32);
+    }
+    ENTITIES.iter().find(|(name, _)| *name == entity).and_then(|(_, code)| std::char::from_u32(*code))
+}
+
+/// Replace the character references (`&amp;`, `&#233;`, `&#xE9;`...) of a text by the characters they represent.
+/// Unknown references are kept as they are.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        decoded.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        // Entity names are short, do not search the end of an unterminated one in the whole text
+        let character = rest[1..].char_indices().take(32).find(|(_, c)| *c == ';').and_then(|(end, _)| {
+            decode_entity(&rest[1..end + 1]).map(|c| (c, end + 2))
+        });
+        match character {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// An opening tag, with the content of the element if it is a script.
+#[derive(Debug, PartialEq)]
+pub struct Element {
+    /// In lowercase
+    pub name: String,
+    /// Names are in lowercase and values are decoded
+    pub attributes: Vec<(String, String)>,
+    /// Scripts are raw text, which is not decoded
+    pub text: Option<String>,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(attribute, _)| attribute == name).map(|(_, value)| value.as_str())
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.as_bytes().windows(needle.len()).position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Read the elements of a page, ignoring comments and closing tags.
+pub fn elements(html: &str) -> Vec<Element> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':';
+
+    let mut elements = Vec::new();
+    let mut rest = html;
+    while let Some(idx) = rest.find('<') {
+        rest = &rest[idx + 1..];
+
+        if rest.starts_with("!--") {
+            rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with('/') || rest.starts_with('!') || rest.starts_with('?') {
+            rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+            continue;
+        }
+
+        let name_len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        if name_len == 0 {
+            continue;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        rest = &rest[name_len..];
+
+        let mut attributes = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with('>') || rest.starts_with("/>") {
+                rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+                break;
+            }
+
+            if let Some(after_slash) = rest.strip_prefix('/') {
+                rest = after_slash;
+                continue;
+            }
+
+            // The first character is always part of the name, even if it is `=`
+            let name_len = rest.char_indices().skip(1).find(|(_, c)| c.is_whitespace() || *c == '=' || *c == '>' || *c == '/').map(|(idx, _)| idx).unwrap_or(rest.len());
+            let attribute = rest[..name_len].to_ascii_lowercase();
+            rest = rest[name_len..].trim_start();
+
+            let value = if let Some(after_equal) = rest.strip_prefix('=') {
+                let after_equal = after_equal.trim_start();
+                match after_equal.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        let end = after_equal[1..].find(quote).map(|end| end + 1).unwrap_or(after_equal.len());
+                        rest = after_equal.get(end + 1..).unwrap_or("");
+                        &after_equal[1..end]
+                    }
+                    _ => {
+                        let end = after_equal.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(after_equal.len());
+                        rest = &after_equal[end..];
+                        &after_equal[..end]
+                    }
+                }
+            } else {
+                ""
+            };
+            attributes.push((attribute, decode_entities(value)));
+        }
+
+        let text = if name == "script" || name == "style" {
+            let end = find_ignore_case(rest, &format!("</{}", name)).unwrap_or(rest.len());
+            let text = rest[..end].to_string();
+            rest = &rest[end..];
+            Some(text).filter(|_| name == "script")
+        } else {
+            None
+        };
+
+        elements.push(Element {
+            name,
+            attributes,
+            text,
+        });
+    }
+    elements
+}
+
+/// Get the argument of the call to `function` in an angular expression such as `initContestant({...}); initEntryCount(42)`.
+fn call_argument<'a>(expression: &'a str, function: &str) -> Option<&'a str> {
+    let mut rest = expression;
+    while let Some(idx) = rest.find(function) {
+        let preceded_by_name = rest[..idx].chars().last().map(|c| c.is_alphanumeric() || c == '_' || c == '.').unwrap_or(false);
+        rest = &rest[idx + function.len()..];
+        let arguments = match rest.trim_start().strip_prefix('(') {
+            Some(arguments) if !preceded_by_name => arguments.trim_start(),
+            _ => continue,
+        };
+        if arguments.starts_with(')') {
+            return Some("");
+        }
+
+        if let Some(end) = json_value_len(arguments) {
+            if arguments[end..].trim_start().starts_with(')') {
+                return Some(&arguments[..end]);
+            }
+        }
+    }
+    None
+}
+
+/// Get the length of the JSON value at the start of a text.
+fn json_value_len(text: &str) -> Option<usize> {
+    // The JSON parser does not accept numbers followed by anything but JSON
+    if text.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+        return text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '.')).or(Some(text.len()));
+    }
+
+    // Let the JSON parser find where the value ends, because it can contain parentheses in strings
+    let mut values = serde_json::Deserializer::from_str(text).into_iter::<serde::de::IgnoredAny>();
+    match values.next() {
+        Some(Ok(_)) => Some(values.byte_offset()),
+        _ => None,
+    }
+}
+
+/// Find the JSON data named `name` in a page. It can be:
+///  - given to an angular function in a `ng-init` attribute: `ng-init='initCampaign({...})'`,
+///  - stored in a data attribute: `data-campaign='{...}'`,
+///  - or the content of a JSON script: `<script type="application/json" id="campaign">{...}</script>`.
+pub fn find_json(elements: &[Element], function: &str, name: &str) -> Option<String> {
+    let data_attribute = format!("data-{}", name);
+    for element in elements {
+        if let Some(argument) = element.attribute("ng-init").and_then(|expression| call_argument(expression, function)) {
+            return Some(argument.to_string());
+        }
+        if let Some(value) = element.attribute(&data_attribute) {
+            return Some(value.trim().to_string());
+        }
+        if let Some(text) = &element.text {
+            let is_json = element.attribute("type").map(|t| t.trim().eq_ignore_ascii_case("application/json")).unwrap_or(false);
+            if is_json && element.attribute("id") == Some(name) {
+                return Some(text.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parsing::parse_html, strictness::Strictness};
+
+    #[test]
+    fn entities() {
+        assert_eq!(decode_entities("caf&eacute; &amp; cr&#xE8;me &#8212; 100&#37; &hellip;"), "café & crème — 100% …");
+        assert_eq!(decode_entities("&quot;&apos;&#39;&lt;&gt;&nbsp;"), "\"''<>\u{A0}");
+        assert_eq!(decode_entities("R&D &unknown; &#xZZ; &#0; &"), "R&D &unknown; &#xZZ; \u{FFFD} &");
+    }
+
+    #[test]
+    fn reading_elements() {
+        let elements = elements("<!-- <a href='no'> --><DIV Class=a ng-init=\"f(')>')\" hidden><br/><img src=x.png/><script type='application/json'>{\"a\": \"</div>\"}</script></div>");
+        assert_eq!(elements.len(), 4);
+        assert_eq!(elements[0].name, "div");
+        assert_eq!(elements[0].attributes, vec![
+            (String::from("class"), String::from("a")),
+            (String::from("ng-init"), String::from("f(')>')")),
+            (String::from("hidden"), String::new()),
+        ]);
+        assert_eq!(elements[1].name, "br");
+        assert_eq!(elements[2].attribute("src"), Some("x.png/"));
+        assert_eq!(elements[3].text.as_deref(), Some("{\"a\": \"</div>\"}"));
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(call_argument("initContestant({\"a\": \")'>\"}); initEntryCount(42)", "initEntryCount"), Some("42"));
+        assert_eq!(call_argument("initContestant({\"a\": \")'>\"}); initEntryCount(42)", "initContestant"), Some("{\"a\": \")'>\"}"));
+        assert_eq!(call_argument("initEntryCount( )", "initEntryCount"), Some(""));
+        assert_eq!(call_argument("reinitEntryCount(1)", "initEntryCount"), None);
+        assert_eq!(call_argument("initEntryCount", "initEntryCount"), None);
+    }
+
+    #[test]
+    fn corpus() {
+        let mut pages = std::fs::read_dir("test_data/pages").unwrap().map(|e| e.unwrap().path()).collect::<Vec<_>>();
+        pages.sort();
+        assert!(pages.len() >= 4);
+
+        for page in pages {
+            let html = std::fs::read_to_string(&page).unwrap();
+            let (giveaway, _contestant, entry_count) = match parse_html(&html, Strictness::Strict) {
+                Ok(parsed) => parsed,
+                Err(e) => panic!("failed to parse {:?}: {:?}", page, e),
+            };
+            assert_eq!(giveaway.campaign.key, "7qHd6", "in {:?}", page);
+            assert_eq!(giveaway.campaign.site_name, "Example's blog", "in {:?}", page);
+            assert_eq!(giveaway.incentive.description, "A graphics card :)'> café & crème — 100% <b>free</b>", "in {:?}", page);
+            assert_eq!(entry_count, Some(1337), "in {:?}", page);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+use crate::strictness::*;
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Incentive {
     pub id: usize,
@@ -14,9 +14,16 @@ pub struct Incentive {
     pub layout: String,
     #[serde(flatten)]
     pub image: Option<IncentiveImage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectUnknownFields for Incentive {
+    fn collect_unknown_fields(&self, report: &mut UnknownFields) {
+        report.add("Incentive", &self.extra);
+    }
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IncentiveImage {
     pub url: String,
@@ -25,8 +32,7 @@ pub struct IncentiveImage {
     pub image_width: usize,
 }
 
-#[cfg_attr(debug_assertions, serde(deny_unknown_fields))]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IncompleteIncentive {
     #[serde(rename = "incentive_name")]
     pub name: String,
@@ -5,5 +5,13 @@ pub mod prelude;
 pub mod shortener;
 pub mod contestant;
 pub mod classifier;
+pub mod typed_entry;
 pub mod parsing;
+pub mod html;
 pub mod incentive;
+pub mod strictness;
+pub mod taxonomy;
+pub mod eligibility;
+
+#[cfg(test)]
+mod golden;
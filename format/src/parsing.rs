@@ -1,28 +1,134 @@
-use crate::prelude::*;
-use string_tools::get_all_between_strict as gabs;
+use crate::{prelude::*, strictness::*, html::{self, Element}};
+
+/// The number of bytes shown before and after an error in the JSON.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Data which does not match our structs.
+#[derive(Debug)]
+pub struct FormatError {
+    pub error: serde_json::Error,
+    /// The path of the value which could not be parsed, such as `entry_methods[3].config5`
+    pub path: String,
+    /// The byte offset in the JSON at which the error was detected, usually just after the offending value
+    pub offset: usize,
+    /// The JSON around the error
+    pub snippet: String,
+    /// The key of the campaign, if it could be read
+    pub key: Option<String>,
+    /// The url of the page, if it is known
+    pub url: Option<String>,
+}
+
+impl FormatError {
+    fn new(error: serde_path_to_error::Error<serde_json::Error>, json: &str, key: Option<String>) -> FormatError {
+        let path = error.path().to_string();
+        let error = error.into_inner();
+
+        // Lines are counted from 1, and the column is the one of the last byte read
+        let line_start = json.split('\n').take(error.line().saturating_sub(1)).map(|line| line.len() + 1).sum::<usize>();
+        let offset = (line_start + error.column()).min(json.len());
+        let floor_char_boundary = |mut idx: usize| {
+            while !json.is_char_boundary(idx) {
+                idx -= 1;
+            }
+            idx
+        };
+        let snippet = json[floor_char_boundary(offset.saturating_sub(SNIPPET_CONTEXT))..floor_char_boundary((offset + SNIPPET_CONTEXT).min(json.len()))].to_string();
+
+        FormatError {
+            error,
+            path,
+            offset,
+            snippet,
+            key,
+            url: None,
+        }
+    }
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {} (byte {})", self.error, self.path, self.offset)?;
+        match (&self.key, &self.url) {
+            (_, Some(url)) => write!(f, " in {}", url)?,
+            (Some(key), None) => write!(f, " in campaign {}", key)?,
+            (None, None) => (),
+        }
+        write!(f, ", near `{}`", self.snippet)
+    }
+}
 
 #[derive(Debug)]
 pub enum ParseError {
     GiveawayJsonNotFound,
     ContestantJsonNotFound,
     InvalidEntryCount(std::num::ParseIntError),
-    GiveawayFormatError(serde_json::Error),
-    ContestantFormatError(serde_json::Error),
+    GiveawayFormatError(Box<FormatError>),
+    ContestantFormatError(Box<FormatError>),
+    /// Only in strict mode
+    UnknownFields(UnknownFields),
 }
 
-pub fn parse_html(html: &str) -> Result<(Giveaway, InitContestant, Option<usize>), ParseError> {
-    let json = match gabs(&html, " ng-init='initCampaign(", ")'>") {
-        Some(json) => json,
-        None => return Err(ParseError::GiveawayJsonNotFound),
-    };
-    let json = json.replace("&quot;", "\"").replace("&#39;", "'");
-    
-    let giveaway = match serde_json::from_str::<Giveaway>(&json) {
+impl ParseError {
+    /// Tell where the page comes from, for the error messages.
+    pub fn set_url(&mut self, url: &str) {
+        if let ParseError::GiveawayFormatError(e) | ParseError::ContestantFormatError(e) = self {
+            e.url = Some(url.to_string());
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::GiveawayJsonNotFound => write!(f, "campaign data not found"),
+            ParseError::ContestantJsonNotFound => write!(f, "contestant data not found"),
+            ParseError::InvalidEntryCount(e) => write!(f, "invalid entry count: {}", e),
+            ParseError::GiveawayFormatError(e) => write!(f, "invalid campaign data: {}", e),
+            ParseError::ContestantFormatError(e) => write!(f, "invalid contestant data: {}", e),
+            ParseError::UnknownFields(fields) => write!(f, "unknown fields ({})", fields),
+        }
+    }
+}
+
+/// Parse JSON, keeping track of where it fails.
+fn from_json<T: serde::de::DeserializeOwned>(json: &str, key: impl FnOnce() -> Option<String>) -> Result<T, Box<FormatError>> {
+    serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(json)).map_err(|e| Box::new(FormatError::new(e, json, key())))
+}
+
+/// Read the key of a campaign in JSON which does not match our structs.
+fn campaign_key(json: &str) -> Option<String> {
+    let giveaway = serde_json::from_str::<serde_json::Value>(json).ok()?;
+    giveaway.pointer("/campaign/key")?.as_str().map(String::from)
+}
+
+fn campaign_json(elements: &[Element]) -> Result<String, ParseError> {
+    html::find_json(elements, "initCampaign", "campaign").ok_or(ParseError::GiveawayJsonNotFound)
+}
+
+fn contestant_json(elements: &[Element]) -> Result<String, ParseError> {
+    html::find_json(elements, "initContestant", "contestant").ok_or(ParseError::ContestantJsonNotFound)
+}
+
+/// Get the campaign and contestant data of a page as they are, without checking them against our structs.
+pub fn parse_raw_html(html: &str) -> Result<(serde_json::Value, serde_json::Value), ParseError> {
+    let elements = html::elements(html);
+    let giveaway: serde_json::Value = from_json(&campaign_json(&elements)?, || None).map_err(ParseError::GiveawayFormatError)?;
+    let key = giveaway.pointer("/campaign/key").and_then(|key| key.as_str()).map(String::from);
+    let init_contestant = from_json(&contestant_json(&elements)?, || key).map_err(ParseError::ContestantFormatError)?;
+    Ok((giveaway, init_contestant))
+}
+
+pub fn parse_html(html: &str, strictness: Strictness) -> Result<(Giveaway, InitContestant, Option<usize>), ParseError> {
+    let elements = html::elements(html);
+    let json = campaign_json(&elements)?;
+
+    let giveaway = match from_json::<Giveaway>(&json, || campaign_key(&json)) {
         Ok(g) => g,
         Err(e) => return Err(ParseError::GiveawayFormatError(e)),
     };
 
-    let entry_count = match gabs(html, "initEntryCount(", ")") {
+    let entry_count = match html::find_json(&elements, "initEntryCount", "entry-count") {
         Some(count) if !count.is_empty() => match count.parse() {
             Ok(count) => Some(count),
             Err(e) => return Err(ParseError::InvalidEntryCount(e)),
@@ -30,16 +136,45 @@ pub fn parse_html(html: &str) -> Result<(Giveaway, InitContestant, Option<usize>
         _ => None,
     };
 
-    let contestant_json = match gabs(&html, " ng-init='initContestant(", ");") {
-        Some(json) => json,
-        None => return Err(ParseError::ContestantJsonNotFound),
-    };
-    let contestant_json = contestant_json.replace("&quot;", "\"").replace("&#39;", "'");
+    let contestant_json = contestant_json(&elements)?;
 
-    let init_contestant = match serde_json::from_str::<InitContestant>(&contestant_json) {
+    let init_contestant = match from_json::<InitContestant>(&contestant_json, || Some(giveaway.campaign.key.clone())) {
         Ok(g) => g,
         Err(e) => return Err(ParseError::ContestantFormatError(e)),
     };
-    
+
+    if strictness == Strictness::Strict {
+        let mut unknown_fields = giveaway.unknown_fields();
+        init_contestant.collect_unknown_fields(&mut unknown_fields);
+        if !unknown_fields.is_empty() {
+            return Err(ParseError::UnknownFields(unknown_fields));
+        }
+    }
+
     Ok((giveaway, init_contestant, entry_count))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_errors() {
+        let html = std::fs::read_to_string("test_data/pages/single_quoted.html").unwrap();
+        let broken = html.replacen("&quot;worth&quot;: 1", "&quot;worth&quot;: &quot;one&quot;", 1);
+        let mut error = match parse_html(&broken, Strictness::Lenient) {
+            Err(ParseError::GiveawayFormatError(e)) => e,
+            _ => panic!("the page should not be valid"),
+        };
+        assert_eq!(error.path, "entry_methods[0].worth");
+        assert_eq!(error.key.as_deref(), Some("7qHd6"));
+        assert!(error.snippet.contains("\"worth\": \"one\""));
+        let json = html::find_json(&html::elements(&broken), "initCampaign", "campaign").unwrap();
+        assert!(json[..error.offset].ends_with("\"worth\": \"one\""));
+
+        assert!(error.to_string().contains("at entry_methods[0].worth"));
+        assert!(error.to_string().contains("in campaign 7qHd6"));
+        error.url = Some(String::from("https://gleam.io/7qHd6/-"));
+        assert!(error.to_string().contains("in https://gleam.io/7qHd6/-"));
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How to handle the fields sent by gleam.io which are not in our structs.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Unknown fields are kept in the `extra` map of each struct
+    #[default]
+    Lenient,
+    /// Unknown fields make parsing fail, with a report listing all of them
+    Strict,
+}
+
+/// The unknown fields found in a page, by struct.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UnknownFields {
+    pub fields: BTreeMap<&'static str, BTreeSet<String>>,
+}
+
+impl UnknownFields {
+    pub fn add(&mut self, name: &'static str, extra: &Map<String, Value>) {
+        if !extra.is_empty() {
+            self.fields.entry(name).or_default().extend(extra.keys().cloned());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl std::fmt::Display for UnknownFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let structs = self.fields.iter()
+            .map(|(name, fields)| format!("{}: {}", name, fields.iter().cloned().collect::<Vec<String>>().join(", ")))
+            .collect::<Vec<String>>();
+        write!(f, "{}", structs.join("; "))
+    }
+}
+
+/// Implemented by the structs keeping the fields they do not know.
+/// These structs flatten the fields Gleam sends but we don't parse into an `extra` map, so they are not lost and can be reported.
+pub trait CollectUnknownFields {
+    /// Add the unknown fields of this struct and of the structs it contains to the report.
+    fn collect_unknown_fields(&self, report: &mut UnknownFields);
+
+    fn unknown_fields(&self) -> UnknownFields {
+        let mut report = UnknownFields::default();
+        self.collect_unknown_fields(&mut report);
+        report
+    }
+}
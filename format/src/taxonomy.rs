@@ -0,0 +1,84 @@
+use serde::{Serialize, Deserialize};
+use crate::{classifier::classify, entry::EntryMethod};
+
+/// The name given to the entry methods the classifier does not recognize.
+pub const UNCLASSIFIED: &str = "Unclassified";
+
+/// What a giveaway asks for, to filter giveaways in the search index.
+/// Giveaways saved before it existed have an empty taxonomy until they are updated.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Taxonomy {
+    /// The entry types of the entry methods, sorted and without duplicates
+    #[serde(default)]
+    pub entry_types: Vec<String>,
+    /// The providers of the mandatory entry methods, sorted and without duplicates
+    #[serde(default)]
+    pub required_providers: Vec<String>,
+    #[serde(default)]
+    pub total_worth: usize,
+    #[serde(default)]
+    pub mandatory_actions: usize,
+}
+
+impl From<&[EntryMethod]> for Taxonomy {
+    fn from(entry_methods: &[EntryMethod]) -> Taxonomy {
+        let mut entry_types = entry_methods.iter()
+            .map(|entry_method| classify(entry_method).map(|entry_type| entry_type.to_string()).unwrap_or_else(|| UNCLASSIFIED.to_string()))
+            .collect::<Vec<String>>();
+        entry_types.sort();
+        entry_types.dedup();
+
+        let mut required_providers = entry_methods.iter()
+            .filter(|entry_method| entry_method.mandatory)
+            .map(|entry_method| entry_method.provider.clone())
+            .collect::<Vec<String>>();
+        required_providers.sort();
+        required_providers.dedup();
+
+        Taxonomy {
+            entry_types,
+            required_providers,
+            total_worth: entry_methods.iter().map(|entry_method| entry_method.worth).sum(),
+            mandatory_actions: entry_methods.iter().filter(|entry_method| entry_method.mandatory).count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{giveaway::{IncompleteGiveaway, SearchResult}, parsing::parse_html, strictness::Strictness};
+
+    #[test]
+    fn taxonomy() {
+        let html = std::fs::read_to_string("test_data/corpus/unclassified.html").unwrap();
+        let (mut giveaway, _, _) = parse_html(&html, Strictness::Lenient).unwrap();
+        giveaway.entry_methods[0].mandatory = true;
+        giveaway.entry_methods[2].mandatory = true;
+        giveaway.entry_methods[2].worth = 5;
+
+        // A TikTok follow and a Twitter follow which are not classified, and an Instagram entry
+        let taxonomy = Taxonomy::from(giveaway.entry_methods.as_slice());
+        assert_eq!(taxonomy.entry_types, vec!["InstagramEnter", UNCLASSIFIED]);
+        assert_eq!(taxonomy.required_providers, vec!["instagram", "tiktok"]);
+        assert_eq!(taxonomy.total_worth, 7);
+        assert_eq!(taxonomy.mandatory_actions, 2);
+
+        // Giveaways saved before the taxonomy can still be read
+        let search_result = SearchResult {
+            giveaway: IncompleteGiveaway::from(giveaway),
+            last_updated: 0,
+            failed_at: None,
+            failures: 0,
+            referers: Vec::new(),
+            entry_count: None,
+            entry_evolution: None,
+        };
+        let mut saved = serde_json::to_value(&search_result).unwrap();
+        for field in ["entry_types", "required_providers", "total_worth", "mandatory_actions"].iter() {
+            assert!(saved.as_object_mut().unwrap().remove(*field).is_some());
+        }
+        let saved: SearchResult = serde_json::from_value(saved).unwrap();
+        assert_eq!(saved.giveaway.taxonomy, Taxonomy::default());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{classifier::{classify, EntryType}, entry::EntryMethod};
+use serde::Serialize;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+/// An entry method with the meaning of its configs.
+/// It is built from an `EntryMethod` recognized by the classifier.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum TypedEntry {
+    InstagramEnter { profile_url: String },
+    InstagramViewPost { post_url: String },
+    InstagramVisitProfile { profile_url: String, username: String },
+    InstagramVisitProfileWithQuestion { profile_url: String, username: String, question: String, answers: Vec<String> },
+    CustomActionAskQuestion { text: String },
+    CustomActionQuestion { text: String },
+    CustomActionChooseOption { text: String, options: Vec<String> },
+    CustomActionVisitQuestion { text: String, answers: Vec<String> },
+    CustomActionBlogComment { text: String },
+    CustomActionBasic { text: String },
+    CustomActionVisitAuto { text: String },
+    /// The visit must last `delay` seconds
+    CustomActionVisitDelay { text: String, delay: u64 },
+    CustomActionBonus { text: String },
+    EmailSubscribe { text: String },
+    FacebookEnter { page: String },
+    FacebookVisitComplete { page_url: String, page_name: String },
+    FacebookVisitLike { page_url: String, page_name: String },
+    FacebookVisitWithQuestion { page_url: String, page_name: String, question: String, answers: Vec<String> },
+    FacebookViewPost { post_url: String, page_name: String },
+    PinterestVisitComplete { username: String },
+    PinterestVisitFollow { username: String },
+    TwitterEnter { username: String },
+    TwitterRetweet { tweet_url: String, username: String },
+    TwitterTweet { text: String },
+    TwitterFollow { username: String },
+    YoutubeVisitChannel { channel: String },
+    /// The visit must last `delay` seconds
+    YoutubeVisitChannelWithDelay { channel_url: String, channel_name: String, delay: u64 },
+    YoutubeVisitChannelWithQuestion { channel_url: String, username: String, question: String, answers: Vec<String> },
+    YoutubeEnter { channel: String },
+    TwitchEnter { channel: String },
+    TwitchFollow { channel: String },
+    DiscordJoinServer { text: String, invite_url: String, server_name: String },
+    LinkedInFollow { profile_url: String, name: String },
+    SteamJoinGroup { group_url: String, group_name: String },
+    Loyalty { text: String },
+    ShareAction { text: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TypedEntryError {
+    /// The classifier does not know this kind of entry method
+    Unclassified,
+    /// The entry type comes from a rules file and has no typed configs
+    UnknownEntryType(String),
+    /// A config required by the entry type is missing (from 1 to 9)
+    MissingConfig(usize),
+    /// A config should be a number
+    InvalidNumber(usize),
+}
+
+impl std::fmt::Display for TypedEntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypedEntryError::Unclassified => write!(f, "unknown entry method"),
+            TypedEntryError::UnknownEntryType(name) => write!(f, "no typed configs for {}", name),
+            TypedEntryError::MissingConfig(index) => write!(f, "config{} is missing", index),
+            TypedEntryError::InvalidNumber(index) => write!(f, "config{} is not a number", index),
+        }
+    }
+}
+
+impl TryFrom<&EntryMethod> for TypedEntry {
+    type Error = TypedEntryError;
+
+    fn try_from(entry: &EntryMethod) -> Result<TypedEntry, TypedEntryError> {
+        use TypedEntry::*;
+
+        let text = |index: usize| entry.config(index).cloned().ok_or(TypedEntryError::MissingConfig(index));
+        let number = |index: usize| text(index)?.parse::<u64>().map_err(|_| TypedEntryError::InvalidNumber(index));
+        let list = |index: usize, separator: &str| -> Vec<String> {
+            entry.config(index)
+                .map(|list| list.split(separator).map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect())
+                .unwrap_or_default()
+        };
+
+        Ok(match classify(entry).ok_or(TypedEntryError::Unclassified)? {
+            EntryType::InstagramEnter => InstagramEnter { profile_url: text(1)? },
+            EntryType::InstagramViewPost => InstagramViewPost { post_url: text(1)? },
+            EntryType::InstagramVisitProfile => InstagramVisitProfile { profile_url: text(1)?, username: text(3)? },
+            EntryType::InstagramVisitProfileWithQuestion => InstagramVisitProfileWithQuestion { profile_url: text(1)?, username: text(3)?, question: text(7)?, answers: list(8, ",") },
+            EntryType::CustomActionAskQuestion => CustomActionAskQuestion { text: text(1)? },
+            EntryType::CustomActionQuestion => CustomActionQuestion { text: text(1)? },
+            EntryType::CustomActionChooseOption => CustomActionChooseOption { text: text(1)?, options: list(5, "\r\n") },
+            EntryType::CustomActionVisitQuestion => CustomActionVisitQuestion { text: text(1)?, answers: list(5, ",") },
+            EntryType::CustomActionBlogComment => CustomActionBlogComment { text: text(1)? },
+            EntryType::CustomActionBasic => CustomActionBasic { text: text(1)? },
+            EntryType::CustomActionVisitAuto => CustomActionVisitAuto { text: text(1)? },
+            EntryType::CustomActionVisitDelay => CustomActionVisitDelay { text: text(1)?, delay: number(7)? },
+            EntryType::CustomActionBonus => CustomActionBonus { text: text(1)? },
+            EntryType::EmailSubscribe => EmailSubscribe { text: text(1)? },
+            EntryType::FacebookEnter => FacebookEnter { page: text(1)? },
+            EntryType::FacebookVisitComplete => FacebookVisitComplete { page_url: text(1)?, page_name: text(2)? },
+            EntryType::FacebookVisitLike => FacebookVisitLike { page_url: text(1)?, page_name: text(2)? },
+            EntryType::FacebookVisitWithQuestion => FacebookVisitWithQuestion { page_url: text(1)?, page_name: text(2)?, question: text(7)?, answers: list(8, ",") },
+            EntryType::FacebookViewPost => FacebookViewPost { post_url: text(1)?, page_name: text(2)? },
+            EntryType::PinterestVisitComplete => PinterestVisitComplete { username: text(1)? },
+            EntryType::PinterestVisitFollow => PinterestVisitFollow { username: text(1)? },
+            EntryType::TwitterEnter => TwitterEnter { username: text(1)? },
+            EntryType::TwitterRetweet => TwitterRetweet { tweet_url: text(1)?, username: text(2)? },
+            EntryType::TwitterTweet => TwitterTweet { text: text(1)? },
+            EntryType::TwitterFollow => TwitterFollow { username: text(1)? },
+            EntryType::YoutubeVisitChannel => YoutubeVisitChannel { channel: text(1)? },
+            EntryType::YoutubeVisitChannelWithDelay => YoutubeVisitChannelWithDelay { channel_url: text(1)?, channel_name: text(2)?, delay: number(4)? },
+            EntryType::YoutubeVisitChannelWithQuestion => YoutubeVisitChannelWithQuestion { channel_url: text(1)?, username: text(2)?, question: text(5)?, answers: list(6, ",") },
+            EntryType::YoutubeEnter => YoutubeEnter { channel: text(1)? },
+            EntryType::TwitchEnter => TwitchEnter { channel: text(1)? },
+            EntryType::TwitchFollow => TwitchFollow { channel: text(1)? },
+            EntryType::DiscordJoinServer => DiscordJoinServer { text: text(1)?, invite_url: text(2)?, server_name: text(4)? },
+            EntryType::LinkedInFollow => LinkedInFollow { profile_url: text(1)?, name: text(3)? },
+            EntryType::SteamJoinGroup => SteamJoinGroup { group_url: text(1)?, group_name: text(2)? },
+            EntryType::Loyalty => Loyalty { text: text(1)? },
+            EntryType::ShareAction => ShareAction { text: text(1)? },
+            EntryType::Other(name) => return Err(TypedEntryError::UnknownEntryType(name)),
+        })
+    }
+}
+
+impl TypedEntry {
+    pub fn entry_type(&self) -> EntryType {
+        match self {
+            TypedEntry::InstagramEnter { .. } => EntryType::InstagramEnter,
+            TypedEntry::InstagramViewPost { .. } => EntryType::InstagramViewPost,
+            TypedEntry::InstagramVisitProfile { .. } => EntryType::InstagramVisitProfile,
+            TypedEntry::InstagramVisitProfileWithQuestion { .. } => EntryType::InstagramVisitProfileWithQuestion,
+            TypedEntry::CustomActionAskQuestion { .. } => EntryType::CustomActionAskQuestion,
+            TypedEntry::CustomActionQuestion { .. } => EntryType::CustomActionQuestion,
+            TypedEntry::CustomActionChooseOption { .. } => EntryType::CustomActionChooseOption,
+            TypedEntry::CustomActionVisitQuestion { .. } => EntryType::CustomActionVisitQuestion,
+            TypedEntry::CustomActionBlogComment { .. } => EntryType::CustomActionBlogComment,
+            TypedEntry::CustomActionBasic { .. } => EntryType::CustomActionBasic,
+            TypedEntry::CustomActionVisitAuto { .. } => EntryType::CustomActionVisitAuto,
+            TypedEntry::CustomActionVisitDelay { .. } => EntryType::CustomActionVisitDelay,
+            TypedEntry::CustomActionBonus { .. } => EntryType::CustomActionBonus,
+            TypedEntry::EmailSubscribe { .. } => EntryType::EmailSubscribe,
+            TypedEntry::FacebookEnter { .. } => EntryType::FacebookEnter,
+            TypedEntry::FacebookVisitComplete { .. } => EntryType::FacebookVisitComplete,
+            TypedEntry::FacebookVisitLike { .. } => EntryType::FacebookVisitLike,
+            TypedEntry::FacebookVisitWithQuestion { .. } => EntryType::FacebookVisitWithQuestion,
+            TypedEntry::FacebookViewPost { .. } => EntryType::FacebookViewPost,
+            TypedEntry::PinterestVisitComplete { .. } => EntryType::PinterestVisitComplete,
+            TypedEntry::PinterestVisitFollow { .. } => EntryType::PinterestVisitFollow,
+            TypedEntry::TwitterEnter { .. } => EntryType::TwitterEnter,
+            TypedEntry::TwitterRetweet { .. } => EntryType::TwitterRetweet,
+            TypedEntry::TwitterTweet { .. } => EntryType::TwitterTweet,
+            TypedEntry::TwitterFollow { .. } => EntryType::TwitterFollow,
+            TypedEntry::YoutubeVisitChannel { .. } => EntryType::YoutubeVisitChannel,
+            TypedEntry::YoutubeVisitChannelWithDelay { .. } => EntryType::YoutubeVisitChannelWithDelay,
+            TypedEntry::YoutubeVisitChannelWithQuestion { .. } => EntryType::YoutubeVisitChannelWithQuestion,
+            TypedEntry::YoutubeEnter { .. } => EntryType::YoutubeEnter,
+            TypedEntry::TwitchEnter { .. } => EntryType::TwitchEnter,
+            TypedEntry::TwitchFollow { .. } => EntryType::TwitchFollow,
+            TypedEntry::DiscordJoinServer { .. } => EntryType::DiscordJoinServer,
+            TypedEntry::LinkedInFollow { .. } => EntryType::LinkedInFollow,
+            TypedEntry::SteamJoinGroup { .. } => EntryType::SteamJoinGroup,
+            TypedEntry::Loyalty { .. } => EntryType::Loyalty,
+            TypedEntry::ShareAction { .. } => EntryType::ShareAction,
+        }
+    }
+
+    pub fn get_request_type(&self) -> RequestType<'_> {
+        use TypedEntry::*;
+        use TypedEntry::{TwitterRetweet, TwitterTweet, TwitterFollow};
+        use RequestType::*;
+        use serde_json::Value::*;
+
+        match self {
+            InstagramEnter { .. } => Enter,
+            InstagramViewPost { .. } => Simple(String("Done".to_string()), true, false),
+            InstagramVisitProfile { .. } => Simple(String("V".to_string()), false, false),
+            InstagramVisitProfileWithQuestion { answers, .. } => Answer(answers),
+            CustomActionAskQuestion { .. } => Answer(&[]),
+            CustomActionQuestion { .. } => Answer(&[]),
+            CustomActionVisitQuestion { answers, .. } => Answer(answers),
+            CustomActionChooseOption { options, .. } => Answer(options),
+            CustomActionBlogComment { .. } => TextInput,
+            CustomActionBasic { .. } => Simple(String("Done".to_string()), true, false),
+            CustomActionVisitAuto { .. } => Simple(String("V".to_string()), false, false),
+            CustomActionVisitDelay { delay, .. } => SimpleWithDelay((String("V".to_string()), false, false), *delay),
+            CustomActionBonus { .. } => Simple(Null, false, false),
+            EmailSubscribe { .. } => Simple(Null, false, false),
+            FacebookEnter { .. } => Enter,
+            FacebookVisitComplete { .. } => Simple(String("V".to_string()), false, false),
+            FacebookVisitLike { .. } => Simple(String("V".to_string()), true, false),
+            FacebookVisitWithQuestion { answers, .. } => Answer(answers),
+            FacebookViewPost { .. } => Simple(Null, false, false),
+            PinterestVisitComplete { .. } => Simple(String("V".to_string()), false, false),
+            PinterestVisitFollow { .. } => Simple(String("V".to_string()), true, false),
+            TwitterEnter { .. } => Enter,
+            TwitterRetweet { tweet_url, .. } => RequestType::TwitterRetweet { tweet_url },
+            TwitterTweet { text } => RequestType::TwitterTweet(Some(text)),
+            TwitterFollow { username } => RequestType::TwitterFollow { username },
+            YoutubeVisitChannel { .. } => Simple(String("V".to_string()), false, false),
+            YoutubeVisitChannelWithQuestion { answers, .. } => Answer(answers),
+            YoutubeVisitChannelWithDelay { delay, .. } => SimpleWithDelay((String("V".to_string()), true, false), *delay),
+            YoutubeEnter { .. } => Enter,
+            TwitchEnter { .. } => Enter,
+            TwitchFollow { .. } => Simple(Null, false, false),
+            LinkedInFollow { .. } => Simple(String("V".to_string()), false, false),
+            DiscordJoinServer { .. } => Unimplemented("The bot does not support Discord yet."),
+            SteamJoinGroup { .. } => Unimplemented("The bot does not support Steam groups auto-join yet."),
+            Loyalty { .. } => Unimplemented("The bot does not support loyalty entries yet."),
+            ShareAction { .. } => RequestType::TwitterTweet(None),
+        }
+    }
+}
+
+pub enum RequestType<'a> {
+    Enter,
+    TextInput,
+    /// The first answer should be used, if there is one
+    Answer(&'a [String]),
+    Simple(Value, bool, bool),
+    /// The delay is in seconds
+    SimpleWithDelay((Value, bool, bool), u64),
+    Unimplemented(&'static str),
+    TwitterRetweet { tweet_url: &'a str },
+    TwitterTweet(Option<&'a str>), // the text of the tweet, or None when we must share
+    TwitterFollow { username: &'a str },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parse_html;
+    use crate::strictness::Strictness;
+
+    fn typed_entries(page: &str) -> Vec<Result<TypedEntry, TypedEntryError>> {
+        let html = std::fs::read_to_string(format!("test_data/corpus/{}.html", page)).unwrap();
+        let (giveaway, _, _) = parse_html(&html, Strictness::Lenient).unwrap();
+        giveaway.entry_methods.iter().map(|entry| {
+            let typed_entry = TypedEntry::try_from(entry);
+            if let Ok(typed_entry) = &typed_entry {
+                assert_eq!(Some(typed_entry.entry_type()), classify(entry));
+            }
+            typed_entry
+        }).collect()
+    }
+
+    #[test]
+    fn conversions() {
+        let entries = typed_entries("social_media");
+        assert_eq!(entries[0], Ok(TypedEntry::TwitterFollow { username: String::from("examplebrand") }));
+        assert_eq!(entries[2], Ok(TypedEntry::TwitterTweet { text: String::from("I want to win! #giveaway") }));
+        assert_eq!(entries[5], Ok(TypedEntry::YoutubeVisitChannelWithDelay {
+            channel_url: String::from("https://www.youtube.com/c/example"),
+            channel_name: String::from("Example channel"),
+            delay: 30,
+        }));
+        // The answers are in config8, config6 is a number
+        assert_eq!(entries[8], Ok(TypedEntry::FacebookVisitWithQuestion {
+            page_url: String::from("https://www.facebook.com/example"),
+            page_name: String::from("Example"),
+            question: String::from("Which prize do you want?"),
+            answers: vec![String::from("Keyboard"), String::from("Mouse")],
+        }));
+        assert!(entries.iter().all(|entry| entry.is_ok()));
+
+        let entries = typed_entries("custom_actions");
+        assert_eq!(entries[4], Ok(TypedEntry::CustomActionChooseOption {
+            text: String::from("Pick a colour"),
+            options: vec![String::from("Red"), String::from("Blue")],
+        }));
+        assert!(matches!(entries[2].as_ref().unwrap().get_request_type(), RequestType::SimpleWithDelay(_, 15)));
+
+        let entries = typed_entries("unclassified");
+        assert_eq!(entries[0], Err(TypedEntryError::Unclassified));
+        assert_eq!(entries[1], Err(TypedEntryError::Unclassified));
+        assert!(entries[2].is_ok());
+    }
+}